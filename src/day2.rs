@@ -1,14 +1,18 @@
+use std::fs;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::io;
 use std::fmt;
+use std::convert::TryFrom;
+
+use clap::{Arg, ArgMatches};
 
 use crate::util::error::CatchAllError;
 
 struct Command {
     direction: Direction,
-    length: i32
+    length: i128 // as wide as any --int-width, and narrowed when used
 }
 
 impl fmt::Display for Command {
@@ -34,7 +38,23 @@ impl fmt::Display for Direction {
     }
 }
 
-pub fn run(input_reader: BufReader<File>) {
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("svg")
+            .long("svg")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Day 2: writes the submarine path under both models to an SVG file"),
+        Arg::with_name("int_width")
+            .long("int-width")
+            .takes_value(true)
+            .possible_values(&["32", "64", "128"])
+            .default_value("32")
+            .help("Day 2: integer width used for position arithmetic"),
+    ]
+}
+
+pub fn run(input_reader: BufReader<File>, matches: &ArgMatches) {
     let input_iter = input_reader.lines();
     let input_vec_result: Result<Vec<Command>,_> = input_iter
         .map(parse_line)
@@ -42,11 +62,61 @@ pub fn run(input_reader: BufReader<File>) {
 
     let input_vec = input_vec_result.expect("could not parse input");
 
-    let result_a = day_2a(&input_vec);
-    println!("Day 2; Part A: ({},{}) -> {}", result_a.0, result_a.1, result_a.0 * result_a.1);
+    match matches.value_of("int_width") {
+        Some("64") => run_with::<i64>(&input_vec, matches),
+        Some("128") => run_with::<i128>(&input_vec, matches),
+        _ => run_with::<i32>(&input_vec, matches),
+    }
+}
+
+fn run_with<N: CheckedInt>(input_vec: &[Command], matches: &ArgMatches) {
+    let result_a = day_2a::<N>(input_vec).expect("error during part a");
+    println!("Day 2; Part A: ({},{}) -> {}", result_a.0, result_a.1,
+             product(result_a).expect("error during part a"));
+
+    let result_b = day_2b::<N>(input_vec).expect("error during part b");
+    println!("Day 2; Part B: ({},{}) -> {}", result_b.0, result_b.1,
+             product(result_b).expect("error during part b"));
+
+    if let Some(svg_filename) = matches.value_of("svg") {
+        let svg = svg::render(&[
+            ("plain", &path_2a::<N>(input_vec).expect("error during part a")),
+            ("aim", &path_2b::<N>(input_vec).expect("error during part b")),
+        ]);
+        fs::write(svg_filename, svg)
+            .expect("could not write svg file");
+        println!("Day 2; wrote path svg to {}", svg_filename);
+    }
+}
+
+// the arithmetic the models need, checked so that large inputs report an
+// error instead of silently wrapping
+trait CheckedInt: Copy + Ord + fmt::Display + From<i32> + TryFrom<i128> {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn as_f64(self) -> f64;
+}
+
+macro_rules! impl_checked_int {
+    ($($t:ty),*) => {
+        $(
+            impl CheckedInt for $t {
+                fn checked_add(self, other: Self) -> Option<Self> { <$t>::checked_add(self, other) }
+                fn checked_sub(self, other: Self) -> Option<Self> { <$t>::checked_sub(self, other) }
+                fn checked_mul(self, other: Self) -> Option<Self> { <$t>::checked_mul(self, other) }
+                fn as_f64(self) -> f64 { self as f64 }
+            }
+        )*
+    }
+}
+
+impl_checked_int!(i32, i64, i128);
 
-    let result_b = day_2b(&input_vec);
-    println!("Day 2; Part B: ({},{}) -> {}", result_b.0, result_b.1, result_b.0 * result_b.1);
+fn product<N: CheckedInt>(pos: (N,N)) -> Result<N,CatchAllError> {
+    pos.0.checked_mul(pos.1)
+        .ok_or_else(|| CatchAllError::new(
+                format!("overflow multiplying final position ({},{}); try a wider --int-width", pos.0, pos.1)))
 }
 
 fn parse_line(lr: Result<String,io::Error>) -> Result<Command,CatchAllError> {
@@ -74,42 +144,143 @@ fn parse_line(lr: Result<String,io::Error>) -> Result<Command,CatchAllError> {
 
     let len_str = word_iter.next()
         .ok_or(mk_parse_err())?;
-    let len = len_str.parse::<i32>()
+    let len = len_str.parse::<i128>()
         .map_err(|_| mk_parse_err())?;
 
     return Ok(Command {direction: dir, length: len});
 }
 
-fn day_2a(commands: &[Command]) -> (i32,i32) {
-    let mut hor_pos = 0;
-    let mut depth = 0;
+fn command_length<N: CheckedInt>(idx: usize, command: &Command) -> Result<N,CatchAllError> {
+    N::try_from(command.length)
+        .map_err(|_| CatchAllError::new(format!(
+                    "length of command {} {} doesn't fit the integer width; try a wider --int-width",
+                    idx + 1, command)))
+}
 
-    for command in commands.iter() {
-        match command.direction {
-            Direction::Forward => hor_pos = hor_pos + command.length,
-            Direction::Down => depth = depth + command.length,
-            Direction::Up => depth = depth - command.length,
-        }
+fn overflow_err(model: &str, idx: usize, command: &Command) -> CatchAllError {
+    CatchAllError::new(format!(
+            "overflow in {} model at command {} {}; try a wider --int-width",
+            model, idx + 1, command))
+}
+
+fn day_2a<N: CheckedInt>(commands: &[Command]) -> Result<(N,N),CatchAllError> {
+    Ok(*path_2a(commands)?.last().expect("path always starts at the origin"))
+}
+
+// position after each command under the plain model, starting from the origin
+fn path_2a<N: CheckedInt>(commands: &[Command]) -> Result<Vec<(N,N)>,CatchAllError> {
+    let mut hor_pos = N::from(0);
+    let mut depth = N::from(0);
+    let mut path = vec![(hor_pos, depth)];
+
+    for (idx, command) in commands.iter().enumerate() {
+        let length = command_length::<N>(idx, command)?;
+        let step = match command.direction {
+            Direction::Forward => hor_pos.checked_add(length).map(|h| hor_pos = h),
+            Direction::Down => depth.checked_add(length).map(|d| depth = d),
+            Direction::Up => depth.checked_sub(length).map(|d| depth = d),
+        };
+        step.ok_or_else(|| overflow_err("plain", idx, command))?;
+        path.push((hor_pos, depth));
     }
 
-    return (hor_pos, depth);
+    Ok(path)
+}
+
+fn day_2b<N: CheckedInt>(commands: &[Command]) -> Result<(N,N),CatchAllError> {
+    Ok(*path_2b(commands)?.last().expect("path always starts at the origin"))
 }
 
-fn day_2b(commands: &[Command]) -> (i32,i32) {
-    let mut hor_pos = 0;
-    let mut depth = 0;
-    let mut aim = 0;
+// position after each command under the aim model, starting from the origin
+fn path_2b<N: CheckedInt>(commands: &[Command]) -> Result<Vec<(N,N)>,CatchAllError> {
+    let mut hor_pos = N::from(0);
+    let mut depth = N::from(0);
+    let mut aim = N::from(0);
+    let mut path = vec![(hor_pos, depth)];
+
+    for (idx, command) in commands.iter().enumerate() {
+        let length = command_length::<N>(idx, command)?;
+        let step = match command.direction {
+            Direction::Forward => hor_pos.checked_add(length)
+                .zip(aim.checked_mul(length).and_then(|d| depth.checked_add(d)))
+                .map(|(h,d)| {
+                    hor_pos = h;
+                    depth = d;
+                }),
+            Direction::Down => aim.checked_add(length).map(|a| aim = a),
+            Direction::Up => aim.checked_sub(length).map(|a| aim = a),
+        };
+        step.ok_or_else(|| overflow_err("aim", idx, command))?;
+        path.push((hor_pos, depth));
+    }
+
+    Ok(path)
+}
+
+mod svg {
+    use super::CheckedInt;
+
+    const PANEL_WIDTH: f64 = 600.0;
+    const PANEL_HEIGHT: f64 = 400.0;
+    const MARGIN: f64 = 40.0;
+    const COLORS: [&str; 2] = ["steelblue", "darkorange"];
 
-    for command in commands.iter() {
-        match command.direction {
-            Direction::Forward => {
-                hor_pos = hor_pos + command.length;
-                depth = depth + aim * command.length;
-            },
-            Direction::Down => aim = aim + command.length,
-            Direction::Up => aim = aim - command.length,
+    // renders each named path in its own panel, side by side. each panel is
+    // scaled independently since the two models reach very different depths.
+    pub fn render<N: CheckedInt>(paths: &[(&str, &[(N,N)])]) -> String {
+        let width = PANEL_WIDTH * paths.len() as f64;
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+            width, PANEL_HEIGHT, width, PANEL_HEIGHT);
+
+        for (n, (name, path)) in paths.iter().enumerate() {
+            let color = COLORS[n % COLORS.len()];
+            out.push_str(&format!("<g transform=\"translate({},0)\">\n", PANEL_WIDTH * n as f64));
+            out.push_str(&panel(name, path, color));
+            out.push_str("</g>\n");
         }
+
+        out.push_str("</svg>\n");
+        out
     }
 
-    return (hor_pos, depth);
+    fn panel<N: CheckedInt>(name: &str, path: &[(N,N)], color: &str) -> String {
+        let scale = |v: N, min: N, max: N, size: f64| -> f64 {
+            if max == min {
+                MARGIN
+            } else {
+                // go through f64 so the span itself can't overflow
+                MARGIN + (v.as_f64() - min.as_f64()) / (max.as_f64() - min.as_f64()) * (size - 2.0 * MARGIN)
+            }
+        };
+        let min_x = path.iter().map(|p| p.0).min().unwrap_or_else(|| N::from(0));
+        let max_x = path.iter().map(|p| p.0).max().unwrap_or_else(|| N::from(0));
+        let min_y = path.iter().map(|p| p.1).min().unwrap_or_else(|| N::from(0));
+        let max_y = path.iter().map(|p| p.1).max().unwrap_or_else(|| N::from(0));
+
+        // depth grows downwards, which matches svg's y axis
+        let points: Vec<(f64,f64)> = path.iter()
+            .map(|&(x,y)| (scale(x, min_x, max_x, PANEL_WIDTH), scale(y, min_y, max_y, PANEL_HEIGHT)))
+            .collect();
+
+        let mut out = String::new();
+        out.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"14\">{}: x {}..{}, depth {}..{}</text>\n",
+            MARGIN, MARGIN / 2.0, name, min_x, max_x, min_y, max_y));
+
+        let point_strs: Vec<String> = points.iter()
+            .map(|(x,y)| format!("{:.2},{:.2}", x, y))
+            .collect();
+        out.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\n",
+            color, point_strs.join(" ")));
+
+        for (step, (x,y)) in points.iter().enumerate() {
+            out.push_str(&format!(
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"2\" fill=\"{}\"><title>step {}: ({},{})</title></circle>\n",
+                x, y, color, step, path[step].0, path[step].1));
+        }
+
+        out
+    }
 }
@@ -4,20 +4,52 @@ use std::io::BufRead;
 use std::io;
 use std::cmp::Ordering;
 
+use clap::{Arg, ArgMatches};
+
 use crate::util::error::CatchAllError;
 
-pub fn run(input_reader: BufReader<File>) {
-    let input_iter = input_reader.lines();
-    let input_vec_result: Result<Vec<Vec<Bit>>,_> = input_iter
-        .map(parse_line)
-        .collect();
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let tie_arg = |name: &'a str, long: &'a str, default: &'a str, help: &'a str| {
+        Arg::with_name(name)
+            .long(long)
+            .takes_value(true)
+            .possible_values(&["one", "zero", "error"])
+            .default_value(default)
+            .help(help)
+    };
+    vec![
+        tie_arg("gamma_tie", "gamma-tie", "one",
+                "Day 3: gamma bit to use when a column is tied"),
+        tie_arg("oxygen_tie", "oxygen-tie", "one",
+                "Day 3: bit the oxygen rating keeps when a column is tied"),
+        tie_arg("co2_tie", "co2-tie", "zero",
+                "Day 3: bit the CO2 rating keeps when a column is tied"),
+        Arg::with_name("trace")
+            .long("trace")
+            .takes_value(true)
+            .possible_values(&["table", "json"])
+            .help("Day 3: prints each elimination step of the life support ratings"),
+    ]
+}
 
-    let input_vec = input_vec_result.expect("could not parse input");
-    
-    let result_a = day_3a(&input_vec);
+pub fn run(input_reader: BufReader<File>, matches: &ArgMatches) {
+    let mut diagnostics = Diagnostics::new();
+    for lr in input_reader.lines() {
+        parse_line(&mut diagnostics, lr).expect("could not parse input");
+    }
+
+    let tie_policy = |name: &str| TiePolicy::from_arg(matches.value_of(name));
+
+    let result_a = day_3a(&diagnostics, tie_policy("gamma_tie")).expect("error during part a");
     println!("Day 3; Part A: {}", result_a);
 
-    let result_b = day_3b(&input_vec);
+    let oxy_criterion = Criterion { name: "oxygen", keep: Commonality::Most, tie: tie_policy("oxygen_tie") };
+    let co2_criterion = Criterion { name: "CO2", keep: Commonality::Least, tie: tie_policy("co2_tie") };
+    if let Some(format) = matches.value_of("trace") {
+        print!("{}", trace::render(&diagnostics, &[&oxy_criterion, &co2_criterion], format));
+    }
+
+    let result_b = day_3b(&diagnostics, &oxy_criterion, &co2_criterion).expect("error during part b");
     println!("Day 3; Part B: {}", result_b);
 }
 
@@ -36,9 +68,180 @@ impl Bit {
     }
 }
 
-fn parse_line(lr: Result<String,io::Error>) -> Result<Vec<Bit>,CatchAllError> {
+// what to do when a column has as many ones as zeros
+#[derive(Copy,Clone)]
+enum TiePolicy {
+    Prefer(Bit),
+    Error
+}
+
+impl TiePolicy {
+    fn from_arg(arg: Option<&str>) -> TiePolicy {
+        match arg {
+            Some("zero") => TiePolicy::Prefer(Bit::Zero),
+            Some("error") => TiePolicy::Error,
+            _ => TiePolicy::Prefer(Bit::One)
+        }
+    }
+
+    fn resolve(self, common: Option<Bit>, index: usize, what: &str) -> Result<Bit,CatchAllError> {
+        match (common, self) {
+            (Some(b), _) => Ok(b),
+            (None, TiePolicy::Prefer(b)) => Ok(b),
+            (None, TiePolicy::Error) => Err(CatchAllError::new(
+                    format!("{}: bit {} is tied", what, index)))
+        }
+    }
+}
+
+enum Commonality {
+    Most,
+    Least
+}
+
+// bit criteria for a life support rating
+struct Criterion {
+    name: &'static str,
+    keep: Commonality,
+    tie: TiePolicy
+}
+
+impl Criterion {
+    fn keep_bit(&self, common: Option<Bit>, index: usize) -> Result<Bit,CatchAllError> {
+        // the tie policy names the bit to keep, so it applies after negation
+        let bit = match (&self.keep, common) {
+            (Commonality::Least, Some(b)) => Some(b.negate()),
+            (_, common) => common
+        };
+        self.tie.resolve(bit, index, &format!("{} rating", self.name))
+    }
+}
+
+// a growable set of bits packed 64 to a word. bits past len are always zero.
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+    len: usize
+}
+
+impl BitSet {
+    fn new() -> BitSet {
+        BitSet { words: Vec::new(), len: 0 }
+    }
+
+    fn full(len: usize) -> BitSet {
+        let mut words = vec![u64::MAX; len.div_ceil(64)];
+        if !len.is_multiple_of(64) {
+            if let Some(last) = words.last_mut() {
+                *last = (1 << (len % 64)) - 1;
+            }
+        }
+        BitSet { words, len }
+    }
+
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // number of bits set in both self and other
+    fn count_ones_and(&self, other: &BitSet) -> usize {
+        self.words.iter().zip(other.words.iter())
+            .map(|(a,b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    // keeps the bits of self whose value in other equals keep
+    fn retain(&mut self, other: &BitSet, keep: bool) {
+        for (a,b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= if keep { *b } else { !*b };
+        }
+    }
+
+    fn first_one(&self) -> Option<usize> {
+        self.words.iter().enumerate()
+            .find(|(_,w)| **w != 0)
+            .map(|(i,w)| i * 64 + w.trailing_zeros() as usize)
+    }
+
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len).filter(move |&i| self.get(i))
+    }
+}
+
+// all readings, stored column-wise: columns[j] has bit i set when reading i
+// has a one at position j (position 0 being the most significant). this makes
+// counting a column over any subset of readings a popcount.
+struct Diagnostics {
+    width: usize,
+    len: usize,
+    columns: Vec<BitSet>
+}
+
+impl Diagnostics {
+    fn new() -> Diagnostics {
+        Diagnostics { width: 0, len: 0, columns: Vec::new() }
+    }
+
+    fn push(&mut self, bits: &[Bit]) -> Result<(),CatchAllError> {
+        if self.len == 0 {
+            self.width = bits.len();
+            self.columns = vec![BitSet::new(); bits.len()];
+        } else if bits.len() != self.width {
+            return Err(CatchAllError::new(format!(
+                        "reading {} has {} bits, expected {}", self.len + 1, bits.len(), self.width)));
+        }
+
+        for (column, bit) in self.columns.iter_mut().zip(bits) {
+            column.push(*bit == Bit::One);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn all(&self) -> BitSet {
+        BitSet::full(self.len)
+    }
+
+    // returns (ones, zeros) at index among the candidate readings
+    fn column_counts(&self, candidates: &BitSet, index: usize) -> (usize, usize) {
+        let ones = self.columns[index].count_ones_and(candidates);
+        (ones, candidates.count_ones() - ones)
+    }
+
+    // returns the most common bit at index among the candidate readings
+    fn common_bit(&self, candidates: &BitSet, index: usize) -> Option<Bit> {
+        let (ones, zeros) = self.column_counts(candidates, index);
+        match ones.cmp(&zeros) {
+            Ordering::Greater => Some(Bit::One),
+            Ordering::Equal => None,
+            Ordering::Less => Some(Bit::Zero)
+        }
+    }
+
+    fn reading(&self, i: usize) -> Vec<Bit> {
+        self.columns.iter()
+            .map(|c| if c.get(i) { Bit::One } else { Bit::Zero })
+            .collect()
+    }
+}
+
+fn parse_line(diagnostics: &mut Diagnostics, lr: Result<String,io::Error>) -> Result<(),CatchAllError> {
     let l = lr
-        .map_err(|err| CatchAllError::new(format!("io error reading input iter: {}", err.to_string())))?;
+        .map_err(|err| CatchAllError::new(format!("io error reading input iter: {}", err)))?;
 
     let mut bit_vec: Vec<Bit> = Vec::new();
 
@@ -53,111 +256,186 @@ fn parse_line(lr: Result<String,io::Error>) -> Result<Vec<Bit>,CatchAllError> {
         bit_vec.push(r_bit?);
     }
 
-    return Ok(bit_vec);
+    diagnostics.push(&bit_vec)
 }
 
-fn day_3a(input: &[Vec<Bit>]) -> i32 {
-    let borrowed_vec: Vec<&Vec<Bit>> = input.iter().collect();
+fn day_3a(diagnostics: &Diagnostics, tie: TiePolicy) -> Result<u128,CatchAllError> {
+    if diagnostics.len == 0 {
+        return Err(CatchAllError::new("no readings input".to_string()));
+    }
 
-    let common_bits: Vec<Bit> = find_common_bits(&borrowed_vec[..]).iter()
-        .map(|opt_b| *(opt_b.as_ref().unwrap_or(&Bit::One))) // ties are undefined behavior, just pick something
-        .collect();
+    let common_bits: Vec<Bit> = find_common_bits(diagnostics, &diagnostics.all()).iter()
+        .enumerate()
+        .map(|(index, opt_b)| tie.resolve(*opt_b, index, "gamma"))
+        .collect::<Result<_,_>>()?;
 
     let uncommon_bits: Vec<Bit> = common_bits.iter()
         .map(|b| b.negate())
         .collect();
 
-    let gamma = bits_to_int(&common_bits);
-    let epsilon = bits_to_int(&uncommon_bits);
+    let gamma = bits_to_int(&common_bits)?;
+    let epsilon = bits_to_int(&uncommon_bits)?;
 
     println!("gamma: {}", gamma);
     println!("epsilon: {}", epsilon);
 
-    return gamma * epsilon;
+    checked_product(gamma, epsilon)
 }
 
 // returns the most common bit for each position
-fn find_common_bits(bit_vecs: &[&Vec<Bit>]) -> Vec<Option<Bit>> {
-    let mut iter = bit_vecs.iter();
-
-    let first = iter.next().expect("should have at least one reading");
-
-    let mut accum: Vec<i32> = Vec::new();
-    for bit in *first {
-        match bit {
-            Bit::One => accum.push(1),
-            Bit::Zero => accum.push(-1)
-        }
-    }
-
-    for vec in iter {
-        for i in 0..vec.len() {
-            match vec[i] {
-                Bit::One => accum[i] = accum[i] + 1,
-                Bit::Zero => accum[i] = accum[i] - 1
-            }
-        }
-    }
-
-    return accum.iter()
-        .map(|i| match i.cmp(&0) {
-            Ordering::Greater => Some(Bit::One),
-            Ordering::Equal => None,
-            Ordering::Less => Some(Bit::Zero)
-        })
-        .collect();
+fn find_common_bits(diagnostics: &Diagnostics, candidates: &BitSet) -> Vec<Option<Bit>> {
+    (0..diagnostics.width)
+        .map(|index| diagnostics.common_bit(candidates, index))
+        .collect()
 }
 
-fn bits_to_int(bs: &[Bit]) -> i32 {
+fn bits_to_int(bs: &[Bit]) -> Result<u128,CatchAllError> {
+    if bs.len() > 128 {
+        return Err(CatchAllError::new(format!("{} bit readings don't fit in a u128", bs.len())));
+    }
     let mut r = 0;
     for b in bs {
-        r = r << 1;
+        r <<= 1;
         if let Bit::One = b {
-            r = r + 1;
+            r += 1;
         }
     }
-    return r;
+    Ok(r)
 }
 
-fn day_3b(input: &[Vec<Bit>]) -> i32 {
-   let oxy_rating = determine_rating(
-       input,
-       |b| match b {
-           Some(b) => b,
-           None => Bit::One
-       });
+fn checked_product(a: u128, b: u128) -> Result<u128,CatchAllError> {
+    a.checked_mul(b)
+        .ok_or_else(|| CatchAllError::new(format!("product of {} and {} overflows a u128", a, b)))
+}
 
-   let co2_rating = determine_rating(
-       input,
-       |b| match b {
-           Some(b) => b.negate(),
-           None => Bit::Zero
-       });
+fn day_3b(diagnostics: &Diagnostics, oxy_criterion: &Criterion, co2_criterion: &Criterion) ->
+    Result<u128,CatchAllError>
+{
+   let oxy_rating = determine_rating(diagnostics, oxy_criterion, None)?;
+   let co2_rating = determine_rating(diagnostics, co2_criterion, None)?;
 
    println!("oxy_rating: {}", oxy_rating);
    println!("co2_rating: {}", co2_rating);
 
-   return oxy_rating * co2_rating;
+   checked_product(oxy_rating, co2_rating)
 }
 
-fn determine_rating(input: &[Vec<Bit>], criterion: fn(Option<Bit>) -> Bit) -> i32 {
-   let mut candidates: Vec<&Vec<Bit>> = input.iter().collect();
+// one round of filtering in determine_rating
+struct TraceStep {
+    index: usize,
+    ones: usize,
+    zeros: usize,
+    kept: Bit,
+    survivors: Vec<usize>
+}
+
+fn determine_rating(diagnostics: &Diagnostics, criterion: &Criterion, mut trace: Option<&mut Vec<TraceStep>>) ->
+    Result<u128,CatchAllError>
+{
+   let mut candidates = diagnostics.all();
+   let mut remaining = candidates.count_ones();
    let mut index = 0;
 
-   // undefined behavior for running out of numbers, so we skip bounds check for index
-   while candidates.len() > 1 {
-       let common_bits = find_common_bits(&candidates[..]);
-       
-       let keep_bit = criterion(common_bits[index]);
+   while remaining > 1 {
+       if index == diagnostics.width {
+           return Err(CatchAllError::new(format!(
+                       "{} rating: {} candidates remain after all {} bits",
+                       criterion.name, remaining, diagnostics.width)));
+       }
+
+       let (ones, zeros) = diagnostics.column_counts(&candidates, index);
+       let keep_bit = criterion.keep_bit(diagnostics.common_bit(&candidates, index), index)?;
+
+       candidates.retain(&diagnostics.columns[index], keep_bit == Bit::One);
+       remaining = candidates.count_ones();
 
-       candidates = candidates.iter()
-           .map(|v| *v)
-           .filter(|v| v[index] == keep_bit)
-           .collect();
+       if let Some(steps) = trace.as_mut() {
+           steps.push(TraceStep { index, ones, zeros, kept: keep_bit, survivors: candidates.ones().collect() });
+       }
 
-       index = index + 1;
+       index += 1;
    }
 
-   let rating_bits = candidates[0];
-   return bits_to_int(rating_bits);
+   let rating_idx = candidates.first_one()
+       .ok_or_else(|| CatchAllError::new(match index {
+           0 => format!("{} rating: no readings input", criterion.name),
+           _ => format!("{} rating: filtering on bit {} left no candidates", criterion.name, index - 1)
+       }))?;
+   bits_to_int(&diagnostics.reading(rating_idx))
+}
+
+mod trace {
+    use super::Bit;
+    use super::Criterion;
+    use super::Diagnostics;
+    use super::TraceStep;
+    use super::determine_rating;
+
+    fn bit_char(bit: Bit) -> char {
+        match bit {
+            Bit::One => '1',
+            Bit::Zero => '0'
+        }
+    }
+
+    fn reading_str(diagnostics: &Diagnostics, i: usize) -> String {
+        diagnostics.reading(i).into_iter().map(bit_char).collect()
+    }
+
+    // runs determine_rating for each criterion, rendering every filtering step
+    // in the given format. failures are included in the output, since that's
+    // usually why you'd want a trace.
+    pub fn render(diagnostics: &Diagnostics, criteria: &[&Criterion], format: &str) -> String {
+        let traces: Vec<(&str, Vec<TraceStep>, String)> = criteria.iter()
+            .map(|criterion| {
+                let mut steps = Vec::new();
+                let outcome = match determine_rating(diagnostics, criterion, Some(&mut steps)) {
+                    Ok(rating) => format!("{}", rating),
+                    Err(err) => format!("{}", err)
+                };
+                (criterion.name, steps, outcome)
+            })
+            .collect();
+
+        match format {
+            "json" => render_json(diagnostics, &traces),
+            _ => render_table(diagnostics, &traces)
+        }
+    }
+
+    fn render_table(diagnostics: &Diagnostics, traces: &[(&str, Vec<TraceStep>, String)]) -> String {
+        let mut out = String::new();
+        for (name, steps, outcome) in traces {
+            out.push_str(&format!("{} rating\n", name));
+            out.push_str(&format!("{:>4} {:>8} {:>8} {:>5}  survivors\n", "bit", "ones", "zeros", "kept"));
+            for step in steps {
+                let survivors: Vec<String> = step.survivors.iter()
+                    .map(|&i| reading_str(diagnostics, i))
+                    .collect();
+                out.push_str(&format!("{:>4} {:>8} {:>8} {:>5}  {}\n",
+                                      step.index, step.ones, step.zeros, bit_char(step.kept), survivors.join(" ")));
+            }
+            out.push_str(&format!("result: {}\n\n", outcome));
+        }
+        out
+    }
+
+    fn render_json(diagnostics: &Diagnostics, traces: &[(&str, Vec<TraceStep>, String)]) -> String {
+        let ratings: Vec<String> = traces.iter()
+            .map(|(name, steps, outcome)| {
+                let steps: Vec<String> = steps.iter()
+                    .map(|step| {
+                        let survivors: Vec<String> = step.survivors.iter()
+                            .map(|&i| format!("\"{}\"", reading_str(diagnostics, i)))
+                            .collect();
+                        format!("{{\"bit\":{},\"ones\":{},\"zeros\":{},\"kept\":{},\"survivors\":[{}]}}",
+                                step.index, step.ones, step.zeros, bit_char(step.kept), survivors.join(","))
+                    })
+                    .collect();
+                format!("\"{}\":{{\"steps\":[{}],\"result\":\"{}\"}}",
+                        name, steps.join(","), outcome.replace('\\', "\\\\").replace('"', "\\\""))
+            })
+            .collect();
+        format!("{{{}}}\n", ratings.join(","))
+    }
 }
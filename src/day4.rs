@@ -1,9 +1,14 @@
 use std::io::BufReader;
 use std::io::Read;
 use std::fs::File;
+use std::cmp;
+use std::collections::HashMap;
+use std::time::Duration;
 
 use log::debug;
 
+use clap::{Arg, ArgMatches};
+
 use nom::{
     Finish,
     IResult,
@@ -33,32 +38,157 @@ use crate::util::parse::{
 
 use crate::util::error::CatchAllError;
 
-pub fn run(mut input_reader: BufReader<File>) {
+mod server;
+
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("win_length")
+            .long("win-length")
+            .takes_value(true)
+            .value_name("N")
+            .help("Day 4: consecutive marks needed along a row, column or diagonal to win (defaults to the full line)"),
+        Arg::with_name("win_rule")
+            .long("win-rule")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .default_value("rows,columns")
+            .help("Day 4: ways a board can win, any of which counts: rows, columns, diagonals, corners, \
+                   blackout, or pattern:<rows> marking cells with x and separating rows with /, \
+                   e.g. pattern:x...x/...../..x../...../x...x"),
+        Arg::with_name("timeline")
+            .long("timeline")
+            .help("Day 4: prints every board's win in order, and the boards that never win"),
+        Arg::with_name("odds")
+            .long("odds")
+            .takes_value(true)
+            .value_name("TRIALS")
+            .help("Day 4: estimates each board's odds of winning first by replaying shuffled draws"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .default_value("2021")
+            .help("Day 4: seed for --odds shuffles"),
+        Arg::with_name("replay")
+            .long("replay")
+            .help("Day 4: replays the game in the terminal one draw at a time (press enter to step, q to quit)"),
+        Arg::with_name("replay_delay")
+            .long("replay-delay")
+            .takes_value(true)
+            .value_name("MS")
+            .requires("replay")
+            .help("Day 4: steps the replay automatically every MS milliseconds instead of waiting for enter"),
+    ]
+}
+
+pub fn serve_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("port")
+            .long("port")
+            .takes_value(true)
+            .default_value("7878")
+            .help("Local port to listen on"),
+        Arg::with_name("players")
+            .long("players")
+            .takes_value(true)
+            .default_value("1")
+            .help("Number of players to wait for before drawing"),
+        Arg::with_name("interval")
+            .long("interval")
+            .takes_value(true)
+            .value_name("MS")
+            .default_value("2000")
+            .help("Time between draws"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .help("Shuffles the draws with this seed instead of using the input order"),
+    ]
+}
+
+pub fn run(input_reader: BufReader<File>, matches: &ArgMatches) {
+    let (draws, boards) = match read_input(input_reader) {
+        Ok(input) => input,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    let win_rules = win_rules_from(matches).expect("invalid win rules");
+    
+    let game = Game::new(&boards, &win_rules);
+
+    if matches.is_present("replay") {
+        let delay = matches.value_of("replay_delay")
+            .map(|ms| ms.parse::<u64>().expect("replay-delay must be an int"))
+            .map(Duration::from_millis);
+        replay::replay(&game, &draws, delay).expect("error during replay");
+    }
+
+    let timeline = game.play(&draws).expect("error playing game");
+
+    if matches.is_present("timeline") {
+        print!("{}", timeline.pretty());
+    } else if !timeline.never_won.is_empty() {
+        println!("Day 4; {} of {} boards never win (see --timeline)", timeline.never_won.len(), boards.len());
+    }
+    
+    let result_a = day_4a(&timeline).expect("error during part a");
+    println!("Day 4; Part A: {}", result_a);
+
+    let result_b = day_4b(&timeline).expect("error during part b");
+    println!("Day 4; Part B: {}", result_b);
+
+    if let Some(trials_str) = matches.value_of("odds") {
+        let trials = trials_str.parse::<usize>().ok().filter(|&n| n > 0)
+            .expect("odds must be a positive int");
+        let seed = matches.value_of("seed").unwrap_or("2021").parse::<u64>()
+            .expect("seed must be an int");
+        let board_odds = odds::simulate(&game, &draws, trials, seed).expect("error simulating odds");
+        print!("{}", odds::pretty(&board_odds, trials));
+    }
+}
+
+pub fn serve_bingo(input_reader: BufReader<File>, matches: &ArgMatches, serve_matches: &ArgMatches) ->
+    Result<(),CatchAllError>
+{
+    let (draws, boards) = read_input(input_reader)?;
+    let win_rules = win_rules_from(matches)?;
+    server::serve(&draws, &boards, &win_rules, serve_matches)
+}
+
+fn read_input(mut input_reader: BufReader<File>) -> Result<(Vec<i32>, Vec<Board>),CatchAllError> {
     let mut input_str = String::new();
     input_reader.read_to_string(&mut input_str)
-        .expect("could not read input");
+        .map_err(|err| CatchAllError::new(format!("could not read input: {}", err)))?;
     
     let (unparsed, draws) = parse_draws(&input_str)
-        .expect("could not parse draws");
+        .map_err(|err| CatchAllError::new(format!("could not parse draws: {}", err)))?;
 
     debug!("draws: {:?}", draws);
     debug!("unparsed: {}", unparsed);
 
-    let board_parse_result = separated_list0(newline,parse_board)(&unparsed).finish();
-    if board_parse_result.is_err() {
-        println!("{}", convert_error(unparsed, board_parse_result.err().unwrap()));
-        return;
-    }
-    let (unparsed2, boards) = board_parse_result.ok().unwrap();
+    let (unparsed2, boards) = separated_list0(newline,parse_board)(unparsed).finish()
+        .map_err(|err| CatchAllError::new(convert_error(unparsed, err)))?;
 
     debug!("boards: {:?}", boards);
     debug!("unparsed2: {}", unparsed2);
-    
-    let result_a = day_4a(&draws, &boards).expect("error during part a");
-    println!("Day 4; Part A: {}", result_a);
 
-    let result_b = day_4b(&draws, &boards).expect("error during part b");
-    println!("Day 4; Part B: {}", result_b);
+    validate_boards(&boards)?;
+
+    Ok((draws, boards))
+}
+
+fn win_rules_from(matches: &ArgMatches) -> Result<WinRules,CatchAllError> {
+    let win_length: Option<usize> = matches.value_of("win_length")
+        .map(|s| s.parse::<usize>().ok().filter(|&n| n > 0)
+             .ok_or_else(|| CatchAllError::new("win-length must be a positive int".to_string())))
+        .transpose()?;
+    let rules: Vec<WinRule> = matches.values_of("win_rule")
+        .map(|vals| vals.map(WinRule::from_arg).collect::<Result<_,_>>())
+        .unwrap_or_else(|| Ok(vec![WinRule::Rows, WinRule::Columns]))?;
+    Ok(WinRules { rules, win_length })
 }
 
 fn parse_draws(input: &str) -> IResult<&str, Vec<i32>, VerboseError<&str>> {
@@ -82,96 +212,396 @@ fn parse_board(input: &str) -> IResult<&str, Board, VerboseError<&str>> {
     terminated(separated_list0(newline,parse_row), newline)(input)
 }
 
-fn day_4a(draws: &[i32], boards: &[Board]) -> Result<i32,CatchAllError> {
-    let mut board_states: Vec<(&Board, BoardCounts)> = boards.iter()
-        .map(|b| (b,BoardCounts::new()))
-        .collect();
-
-    let mut winning_board_idx = 0;
-    let mut winning_draw = 0;
-
-    'outer: for draw in draws {
-        for i in 0..board_states.len() {
-            let board = board_states[i].0;
-            let state = &mut (board_states[i].1);
-            
-            let opt_idx = draw_idx(*draw, board, (*board).len());
-            match opt_idx {
-                Some(idx) => {
-                    state.track_draw(idx);
-                    if state.is_win() {
-                        winning_board_idx = i;
-                        winning_draw = *draw;
-                        break 'outer;
-                    }
-                },
-                None => ()
+// boards may be any rectangular size, but every row must be the same length
+fn validate_boards(boards: &[Board]) -> Result<(),CatchAllError> {
+    for (i, board) in boards.iter().enumerate() {
+        let width = board.first()
+            .map(|row| row.len())
+            .ok_or_else(|| CatchAllError::new(format!("board {} is empty", i + 1)))?;
+        for (j, row) in board.iter().enumerate() {
+            if row.len() != width {
+                return Err(CatchAllError::new(format!(
+                            "board {}: row {} has {} numbers, expected {}",
+                            i + 1, j + 1, row.len(), width)));
             }
         }
-    };
+    }
+    Ok(())
+}
+
+fn day_4a(timeline: &Timeline) -> Result<i64,CatchAllError> {
+    timeline.wins.first()
+        .map(|win| win.score)
+        .ok_or_else(|| CatchAllError::new("no board ever wins".to_string()))
+}
+
+fn day_4b(timeline: &Timeline) -> Result<i64,CatchAllError> {
+    timeline.wins.last()
+        .map(|win| win.score)
+        .ok_or_else(|| CatchAllError::new("no board ever wins".to_string()))
+}
+
+// a board's win, as recorded in a game's timeline
+#[derive(Debug)]
+struct Win {
+    board_idx: usize,
+    draw: i32,
+    turn: usize, // 1-based position of the draw in the draw order
+    score: i64,
+    line: String
+}
+
+// every win in the order they happen, and the boards that never win
+struct Timeline {
+    wins: Vec<Win>,
+    never_won: Vec<usize>
+}
+
+impl Timeline {
+    fn pretty(&self) -> String {
+        let mut out = format!("{:>6} {:>6} {:>6} {:>10}  line\n", "board", "turn", "draw", "score");
+        for win in &self.wins {
+            out.push_str(&format!("{:>6} {:>6} {:>6} {:>10}  {}\n",
+                                  win.board_idx, win.turn, win.draw, win.score, win.line));
+        }
+        if !self.never_won.is_empty() {
+            let never_won: Vec<String> = self.never_won.iter().map(|b| b.to_string()).collect();
+            out.push_str(&format!("never won: {}\n", never_won.join(", ")));
+        }
+        out
+    }
+}
 
-    let winning_board_state = &board_states[winning_board_idx];
+// the boards and rules for a game, indexed so it can be played repeatedly
+struct Game<'a> {
+    boards: &'a [Board],
+    win_rules: &'a WinRules,
+    draw_index: DrawIndex
+}
 
-    debug!("winning board: {:?}", winning_board_state.0);
-    debug!("state: {:?}", winning_board_state.1);
-    debug!("pretty:\n{}", winning_board_state.1.pretty(winning_board_state.0));
+impl<'a> Game<'a> {
+    fn new(boards: &'a [Board], win_rules: &'a WinRules) -> Game<'a> {
+        Game { boards, win_rules, draw_index: DrawIndex::new(boards) }
+    }
 
+    fn start(&self) -> Result<GameState<'_>,CatchAllError> {
+        let counts = self.boards.iter()
+            .map(|b| BoardCounts::new(b, self.win_rules))
+            .collect::<Result<_,_>>()?;
+        Ok(GameState { game: self, counts, turn: 0, wins: Vec::new() })
+    }
 
-    let winning_score = winning_board_state.1.compute_score(winning_board_state.0);
+    // plays draws in order until every board has won or the draws run out
+    fn play(&self, draws: &[i32]) -> Result<Timeline,CatchAllError> {
+        let mut state = self.start()?;
+        for &draw in draws {
+            if state.all_won() {
+                break;
+            }
+            state.draw(draw);
+        }
+        Ok(state.into_timeline())
+    }
+}
 
-    return Ok(winning_score * winning_draw);
+struct GameState<'a> {
+    game: &'a Game<'a>,
+    counts: Vec<BoardCounts>,
+    turn: usize,
+    wins: Vec<Win>
 }
 
-fn draw_idx(draw: i32, board: &[Vec<i32>], board_len: usize) ->
-    Option<(usize,usize)>
-{
-    for i in 0..board_len {
-        for j in 0..board[i].len() {
-            if board[i][j] == draw {
-                return Some((i,j));
+impl<'a> GameState<'a> {
+    // marks draw on every board that has it, returning the wins it caused.
+    // boards that have already won are left as they were when they won.
+    fn draw(&mut self, draw: i32) -> &[Win] {
+        self.turn += 1;
+        let first_new = self.wins.len();
+        for &(i, idx) in self.game.draw_index.boards_with(draw) {
+            let board = &self.game.boards[i];
+            let state = &mut self.counts[i];
+            if state.is_win() {
+                continue;
+            }
+            state.track_draw(idx);
+            if let Some(line) = state.winning_line() {
+                let score = state.compute_score(board) as i64 * draw as i64;
+                debug!("board {} wins on turn {} with {}:\n{}", i, self.turn, line.name, state.pretty(board));
+                self.wins.push(Win { board_idx: i, draw, turn: self.turn, score, line: line.name.clone() });
             }
         }
+        &self.wins[first_new..]
+    }
+
+    fn all_won(&self) -> bool {
+        self.wins.len() == self.counts.len()
+    }
+
+    fn into_timeline(self) -> Timeline {
+        let never_won = self.counts.iter().enumerate()
+            .filter(|(_, state)| !state.is_win())
+            .map(|(i, _)| i)
+            .collect();
+        Timeline { wins: self.wins, never_won }
+    }
+}
+
+// number -> position for one board. if a number appears more than once, only
+// its first position (in reading order) is marked.
+fn board_positions(board: &Board) -> HashMap<i32,(usize,usize)> {
+    let mut positions = HashMap::new();
+    for (i, row) in board.iter().enumerate() {
+        for (j, &num) in row.iter().enumerate() {
+            positions.entry(num).or_insert((i,j));
+        }
+    }
+    positions
+}
+
+// (board index, position on that board)
+type Placement = (usize,(usize,usize));
+
+// number -> every placement it has, in board order, so a draw only touches
+// the boards it marks
+struct DrawIndex {
+    boards_by_number: HashMap<i32,Vec<Placement>>
+}
+
+impl DrawIndex {
+    fn new(boards: &[Board]) -> DrawIndex {
+        let mut boards_by_number: HashMap<i32,Vec<Placement>> = HashMap::new();
+        for (b, board) in boards.iter().enumerate() {
+            for (num, pos) in board_positions(board) {
+                boards_by_number.entry(num).or_default().push((b,pos));
+            }
+        }
+        DrawIndex { boards_by_number }
+    }
+
+    fn boards_with(&self, draw: i32) -> &[Placement] {
+        self.boards_by_number.get(&draw)
+            .map(|v| &v[..])
+            .unwrap_or(&[])
     }
-    return None;
+}
+
+#[derive(Debug)]
+enum WinRule {
+    Rows,
+    Columns,
+    Diagonals,
+    Corners,
+    Blackout,
+    Pattern(Vec<(usize,usize)>)
+}
+
+impl WinRule {
+    fn from_arg(arg: &str) -> Result<WinRule,CatchAllError> {
+        match arg {
+            "rows" => Ok(WinRule::Rows),
+            "columns" => Ok(WinRule::Columns),
+            "diagonals" => Ok(WinRule::Diagonals),
+            "corners" => Ok(WinRule::Corners),
+            "blackout" => Ok(WinRule::Blackout),
+            _ => match arg.strip_prefix("pattern:") {
+                Some(pattern) => WinRule::parse_pattern(pattern),
+                None => Err(CatchAllError::new(format!("unknown win rule {}", arg)))
+            }
+        }
+    }
+
+    // rows are separated by '/', with 'x' marking the cells that must be drawn
+    fn parse_pattern(pattern: &str) -> Result<WinRule,CatchAllError> {
+        let mut cells = Vec::new();
+        for (i, row) in pattern.split('/').enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                match c {
+                    'x' | 'X' => cells.push((i,j)),
+                    '.' => (),
+                    _ => return Err(CatchAllError::new(format!(
+                                "unexpected '{}' in pattern {} (use x and .)", c, pattern)))
+                }
+            }
+        }
+        if cells.is_empty() {
+            return Err(CatchAllError::new(format!("pattern {} marks no cells", pattern)));
+        }
+        Ok(WinRule::Pattern(cells))
+    }
+
+    // the lines this rule contributes to a board of the given size
+    fn lines(&self, height: usize, width: usize, win_length: Option<usize>) ->
+        Result<Vec<WinLine>,CatchAllError>
+    {
+        // a full line, or with a win length every run of that many
+        // consecutive cells along it
+        let runs = |name: String, cells: Vec<(usize,usize)>| -> Vec<WinLine> {
+            match win_length {
+                None => vec![WinLine { name, target: cells.len(), cells }],
+                Some(n) => cells.windows(n)
+                    .map(|run| {
+                        let (first, last) = (run[0], run[n - 1]);
+                        WinLine {
+                            name: format!("{} ({},{})-({},{})", name, first.0, first.1, last.0, last.1),
+                            cells: run.to_vec(),
+                            target: n
+                        }
+                    })
+                    .collect()
+            }
+        };
+        let whole = |name: String, mut cells: Vec<(usize,usize)>| {
+            cells.sort_unstable();
+            cells.dedup();
+            WinLine { name, target: cells.len(), cells }
+        };
+        let diagonal = |(i,j): (usize,usize)| -> Vec<(usize,usize)> {
+            (0..).map(|k| (i + k, j + k)).take_while(|&(i,j)| i < height && j < width).collect()
+        };
+        let anti_diagonal = |(i,j): (usize,usize)| -> Vec<(usize,usize)> {
+            (0..=j).map(|k| (i + k, j - k)).take_while(|&(i,_)| i < height).collect()
+        };
+
+        Ok(match self {
+            WinRule::Rows => (0..height)
+                .flat_map(|i| runs(format!("row {}", i), (0..width).map(|j| (i,j)).collect()))
+                .collect(),
+            WinRule::Columns => (0..width)
+                .flat_map(|j| runs(format!("column {}", j), (0..height).map(|i| (i,j)).collect()))
+                .collect(),
+            // without a win length only the two diagonals from the top corners
+            // can be marked in full on a non-square board; with one, any
+            // diagonal long enough holds a run
+            WinRule::Diagonals => match win_length {
+                None => {
+                    let diag_len = cmp::min(height, width);
+                    vec![
+                        whole("diagonal".to_string(), (0..diag_len).map(|k| (k,k)).collect()),
+                        whole("anti-diagonal".to_string(), (0..diag_len).map(|k| (k,width - 1 - k)).collect()),
+                    ]
+                },
+                Some(_) => {
+                    let starts = (0..width).map(|j| (0,j)).chain((1..height).map(|i| (i,0)));
+                    let anti_starts = (0..width).map(|j| (0,j)).chain((1..height).map(|i| (i,width - 1)));
+                    starts.map(|start| ("diagonal", diagonal(start)))
+                        .chain(anti_starts.map(|start| ("anti-diagonal", anti_diagonal(start))))
+                        .flat_map(|(name, cells)| runs(name.to_string(), cells))
+                        .collect()
+                }
+            },
+            WinRule::Corners => vec![
+                whole("corners".to_string(), vec![(0,0), (0,width - 1), (height - 1,0), (height - 1,width - 1)])
+            ],
+            WinRule::Blackout => vec![
+                whole("blackout".to_string(), (0..height).flat_map(|i| (0..width).map(move |j| (i,j))).collect())
+            ],
+            WinRule::Pattern(cells) => {
+                if let Some(&(i,j)) = cells.iter().find(|&&(i,j)| i >= height || j >= width) {
+                    return Err(CatchAllError::new(format!(
+                                "pattern cell ({},{}) is outside a {}x{} board", i, j, height, width)));
+                }
+                vec![whole("pattern".to_string(), cells.clone())]
+            }
+        })
+    }
+}
+
+// the rules in play for a game. win_length only applies to rows, columns and
+// diagonals, where any run of that many consecutive cells wins; the other
+// rules always need every one of their cells.
+struct WinRules {
+    rules: Vec<WinRule>,
+    win_length: Option<usize>
+}
+
+impl WinRules {
+    // a win length no row, column or diagonal on the board can reach would
+    // mean those rules could never win
+    fn check_win_length(&self, height: usize, width: usize) -> Result<(),CatchAllError> {
+        let longest = self.rules.iter()
+            .filter_map(|rule| match rule {
+                WinRule::Rows => Some(width),
+                WinRule::Columns => Some(height),
+                WinRule::Diagonals => Some(cmp::min(height, width)),
+                _ => None
+            })
+            .max();
+        match (self.win_length, longest) {
+            (Some(win_length), Some(longest)) if win_length > longest => Err(CatchAllError::new(format!(
+                        "win length {} is longer than any row, column or diagonal on a {}x{} board",
+                        win_length, height, width))),
+            _ => Ok(())
+        }
+    }
+}
+
+// a set of cells that wins once target of them have been drawn
+#[derive(Debug)]
+struct WinLine {
+    name: String,
+    cells: Vec<(usize,usize)>,
+    target: usize
 }
 
 #[derive(Debug)]
 struct BoardCounts {
-    rows: Vec<i32>,
-    cols: Vec<i32>,
-    diags: Vec<i32>,
+    lines: Vec<WinLine>,
+    line_counts: Vec<usize>,
+    cell_lines: Vec<Vec<Vec<usize>>>, // indexes into lines for each cell
+    won_line: Option<usize>,
+    marked: Vec<Vec<bool>>,
     all_draws: Vec<(usize,usize)>
 }
     
 impl BoardCounts {
-    fn new() -> BoardCounts {
-        BoardCounts{ 
-            rows: vec![0,0,0,0,0], 
-            cols: vec![0,0,0,0,0], 
-            diags: vec![0,0],
-            all_draws: Vec::new()
+    fn new(board: &Board, win_rules: &WinRules) -> Result<BoardCounts,CatchAllError> {
+        let height = board.len();
+        let width = board.first().map(|row| row.len()).unwrap_or(0);
+
+        win_rules.check_win_length(height, width)?;
+
+        let mut lines = Vec::new();
+        for rule in &win_rules.rules {
+            lines.extend(rule.lines(height, width, win_rules.win_length)?);
+        }
+
+        let mut cell_lines = vec![vec![Vec::new(); width]; height];
+        for (k, line) in lines.iter().enumerate() {
+            for &(i,j) in &line.cells {
+                cell_lines[i][j].push(k);
+            }
         }
+
+        Ok(BoardCounts{ 
+            line_counts: vec![0; lines.len()],
+            lines,
+            cell_lines,
+            won_line: None,
+            marked: vec![vec![false; width]; height],
+            all_draws: Vec::new()
+        })
     }
 
     fn track_draw(&mut self, draw: (usize,usize)) {
-        self.rows[draw.0] = self.rows[draw.0] + 1;
-        self.cols[draw.1] = self.cols[draw.1] + 1;
-        /*
-        if draw.0 == draw.1 {
-            self.diags[0] = self.diags[0] + 1;
+        if self.marked[draw.0][draw.1] {
+            return;
         }
-        if draw.0 + draw.1 == 5 {
-            self.diags[1] = self.diags[1] + 1;
+        self.marked[draw.0][draw.1] = true;
+        for &k in &self.cell_lines[draw.0][draw.1] {
+            self.line_counts[k] += 1;
+            if self.won_line.is_none() && self.line_counts[k] >= self.lines[k].target {
+                self.won_line = Some(k);
+            }
         }
-        */
         self.all_draws.push(draw);
     }
 
     fn is_win(&self) -> bool {
-        fn check_vec(v: &[i32]) -> bool {
-            v.iter().any(|&c| c >= 5)
-        }
-        check_vec(&self.rows) || check_vec(&self.cols) // || check_vec(&self.diags)
+        self.won_line.is_some()
+    }
+
+    fn winning_line(&self) -> Option<&WinLine> {
+        self.won_line.map(|k| &self.lines[k])
     }
 
     fn compute_score(&self, board: &Board) -> i32 {
@@ -190,7 +620,7 @@ impl BoardCounts {
         let mut out: String = String::from("");
         for i in 0..board.len() {
             for j in 0..board[i].len() {
-                if self.all_draws.contains(&(i,j)) {
+                if self.marked[i][j] {
                     out.push_str(&format!("({:^2})", board[i][j]));
                 } else {
                     out.push_str(&format!("{:^4}", board[i][j]));
@@ -201,52 +631,185 @@ impl BoardCounts {
         return out;
     }
 }
-    
-fn day_4b(draws: &[i32], boards: &[Board]) -> Result<i32,CatchAllError> {
-    let mut board_states: Vec<(&Board, BoardCounts)> = boards.iter()
-        .map(|b| (b,BoardCounts::new()))
-        .collect();
-
-    let num_boards = board_states.len();
-
-    let mut losing_board_idx = 0;
-    let mut final_draw = 0;
-
-    let mut num_won = 0;
-    let mut win_idxs = Vec::with_capacity(num_boards);
-    for _ in 0..num_boards {
-        win_idxs.push(false);
-    }
-
-    'outer: for draw in draws {
-        for i in 0..board_states.len() {
-            let board = board_states[i].0;
-            let state = &mut (board_states[i].1);
-            
-            let opt_idx = draw_idx(*draw, board, (*board).len());
-            match opt_idx {
-                Some(idx) => {
-                    state.track_draw(idx);
-                    if !win_idxs[i] && state.is_win() {
-                        win_idxs[i] = true;
-                        num_won = num_won + 1;
-                        if num_won == num_boards {
-                            losing_board_idx = i;
-                            final_draw = *draw;
-                            break 'outer
-                        }
-                    }
-                },
-                None => ()
+
+mod odds {
+    use super::Game;
+
+    use crate::util::error::CatchAllError;
+    use crate::util::rng::Rng;
+
+    #[derive(Clone,Default)]
+    pub struct BoardOdds {
+        first: f64, // wins split evenly between boards that tie for first
+        wins: usize,
+        total_turns: usize
+    }
+
+    // replays the game over shuffled copies of the draw pool
+    pub fn simulate(game: &Game, draws: &[i32], trials: usize, seed: u64) ->
+        Result<Vec<BoardOdds>,CatchAllError>
+    {
+        let mut rng = Rng::new(seed);
+        let mut pool = draws.to_vec();
+        let mut board_odds = vec![BoardOdds::default(); game.boards.len()];
+
+        for _ in 0..trials {
+            rng.shuffle(&mut pool);
+            let timeline = game.play(&pool)?;
+
+            if let Some(first_turn) = timeline.wins.first().map(|w| w.turn) {
+                let firsts: Vec<usize> = timeline.wins.iter()
+                    .take_while(|w| w.turn == first_turn)
+                    .map(|w| w.board_idx)
+                    .collect();
+                for &b in &firsts {
+                    board_odds[b].first += 1.0 / firsts.len() as f64;
+                }
+            }
+            for win in &timeline.wins {
+                board_odds[win.board_idx].wins += 1;
+                board_odds[win.board_idx].total_turns += win.turn;
             }
         }
-    };
 
+        Ok(board_odds)
+    }
 
-    let losing_board_state = &board_states[losing_board_idx];
-    let losing_score = losing_board_state.1.compute_score(losing_board_state.0);
+    pub fn pretty(board_odds: &[BoardOdds], trials: usize) -> String {
+        let mut out = format!("{:>6} {:>9} {:>9} {:>10}\n", "board", "p(first)", "p(win)", "mean turn");
+        for (b, odds) in board_odds.iter().enumerate() {
+            let mean_turn = match odds.wins {
+                0 => "-".to_string(),
+                n => format!("{:.2}", odds.total_turns as f64 / n as f64)
+            };
+            out.push_str(&format!("{:>6} {:>9.4} {:>9.4} {:>10}\n",
+                                  b, odds.first / trials as f64, odds.wins as f64 / trials as f64, mean_turn));
+        }
+        out
+    }
+}
 
-    debug!("pretty loser:\n{}", losing_board_state.1.pretty(losing_board_state.0));
+mod replay {
+    use std::env;
+    use std::io;
+    use std::io::Write;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Board;
+    use super::BoardCounts;
+    use super::Game;
+    use super::GameState;
+
+    use crate::util::error::CatchAllError;
+
+    const CLEAR: &str = "\x1b[2J\x1b[H";
+    const NEW_MARK: &str = "\x1b[1;30;43m";
+    const WIN_LINE: &str = "\x1b[1;32m";
+    const RESET: &str = "\x1b[0m";
+
+    // steps through draws, redrawing every board after each one. with no delay
+    // it waits for enter between draws; entering q stops the replay.
+    pub fn replay(game: &Game, draws: &[i32], delay: Option<Duration>) -> Result<(),CatchAllError> {
+        let io_err = |err: io::Error| CatchAllError::new(format!("io error during replay: {}", err));
+        let mut state = game.start()?;
+        let mut announcements: Vec<String> = Vec::new();
+
+        for &draw in draws {
+            if state.all_won() {
+                break;
+            }
+            for win in state.draw(draw) {
+                announcements.push(format!("turn {}: board {} wins on {} with {}, scoring {}",
+                                           win.turn, win.board_idx, win.draw, win.line, win.score));
+            }
 
-    return Ok(losing_score * final_draw);
+            let mut out = String::from(CLEAR);
+            out.push_str(&format!("turn {} of {}: drew {}\n\n", state.turn, draws.len(), draw));
+            out.push_str(&render_boards(&state, draw));
+            for announcement in &announcements {
+                out.push_str(&format!("{}\n", announcement));
+            }
+            print!("{}", out);
+            io::stdout().flush().map_err(io_err)?;
+
+            match delay {
+                Some(delay) => thread::sleep(delay),
+                None => {
+                    let mut line = String::new();
+                    io::stdin().read_line(&mut line).map_err(io_err)?;
+                    if line.trim() == "q" {
+                        break;
+                    }
+                }
+            }
+        }
+
+        println!("replay finished after {} draws; {} of {} boards won",
+                 state.turn, state.wins.len(), game.boards.len());
+        Ok(())
+    }
+
+    // lays boards out side by side, as many per row as fit the terminal
+    fn render_boards(state: &GameState, draw: i32) -> String {
+        let term_width: usize = env::var("COLUMNS").ok()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(80);
+        let rendered: Vec<Vec<(String, usize)>> = state.game.boards.iter().zip(state.counts.iter())
+            .enumerate()
+            .map(|(i, (board, counts))| render_board(i, board, counts, draw))
+            .collect();
+        let board_width = rendered.iter()
+            .flat_map(|lines| lines.iter().map(|(_, width)| *width))
+            .max()
+            .unwrap_or(0) + 2;
+        let per_row = (term_width / board_width).max(1);
+
+        let mut out = String::new();
+        for chunk in rendered.chunks(per_row) {
+            let height = chunk.iter().map(|lines| lines.len()).max().unwrap_or(0);
+            for row in 0..height {
+                for lines in chunk {
+                    let (text, width) = lines.get(row).cloned().unwrap_or_default();
+                    out.push_str(&text);
+                    out.push_str(&" ".repeat(board_width - width));
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // lines of text for one board, with their printed width (ignoring
+    // escape codes)
+    fn render_board(idx: usize, board: &Board, counts: &BoardCounts, draw: i32) -> Vec<(String, usize)> {
+        let win_cells = counts.winning_line().map(|line| &line.cells[..]).unwrap_or(&[]);
+        let header = if counts.is_win() {
+            format!("board {} (won)", idx)
+        } else {
+            format!("board {}", idx)
+        };
+        let mut lines = vec![(header.clone(), header.len())];
+
+        for (i, row) in board.iter().enumerate() {
+            let mut line = String::new();
+            for (j, num) in row.iter().enumerate() {
+                let cell = if counts.marked[i][j] {
+                    format!("({:^2})", num)
+                } else {
+                    format!("{:^4}", num)
+                };
+                if counts.marked[i][j] && *num == draw && counts.all_draws.last() == Some(&(i,j)) {
+                    line.push_str(&format!("{}{}{}", NEW_MARK, cell, RESET));
+                } else if win_cells.contains(&(i,j)) {
+                    line.push_str(&format!("{}{}{}", WIN_LINE, cell, RESET));
+                } else {
+                    line.push_str(&cell);
+                }
+            }
+            lines.push((line, row.len() * 4));
+        }
+        lines
+    }
 }
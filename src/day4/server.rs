@@ -0,0 +1,292 @@
+// a small line-based bingo server for playing the day 4 game with friends.
+//
+// clients send:
+//   JOIN <name>   take the next unused board
+//   BINGO         claim a win, checked against the draws so far
+//   QUIT          leave the game
+//
+// the server sends:
+//   BOARD <n>     followed by the board in the day 4 input format and a blank line
+//   WAITING <joined>/<needed>
+//   START
+//   DRAW <turn> <number>
+//   WIN <name> <score> <line>
+//   REJECT <reason>
+//   ERROR <reason>
+//   GAMEOVER
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use clap::ArgMatches;
+use log::debug;
+
+use super::Board;
+use super::BoardCounts;
+use super::WinRules;
+use super::board_positions;
+
+use crate::util::error::CatchAllError;
+use crate::util::rng::Rng;
+
+// every write happens with the lobby locked, so a client that stops reading
+// can only hold up the rest of the game this long before it's dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+struct Player {
+    name: String,
+    board_idx: usize,
+    stream: TcpStream,
+    won: bool
+}
+
+// everything the connection handlers and the draw loop share
+struct Lobby {
+    players: Vec<Player>,
+    // every accepted connection, joined or not, so all of them can be closed
+    // at game over
+    connections: Vec<TcpStream>,
+    next_board: usize,
+    drawn: Vec<i32>,
+    started: bool,
+    over: bool
+}
+
+impl Lobby {
+    // sends a line to every player, dropping any that have gone away
+    fn broadcast(&mut self, msg: &str) {
+        debug!("broadcast: {}", msg);
+        self.players.retain(|p| writeln!(&p.stream, "{}", msg).is_ok());
+    }
+}
+
+struct Settings<'a> {
+    boards: &'a [Board],
+    win_rules: &'a WinRules,
+    players_needed: usize
+}
+
+pub fn serve(draws: &[i32], boards: &[Board], win_rules: &WinRules, serve_matches: &ArgMatches) ->
+    Result<(),CatchAllError>
+{
+    let parse_arg = |name: &str| -> Result<u64,CatchAllError> {
+        serve_matches.value_of(name).unwrap_or_default().parse::<u64>()
+            .map_err(|_| CatchAllError::new(format!("{} must be an int", name)))
+    };
+    let port = serve_matches.value_of("port").unwrap_or_default().parse::<u16>()
+        .map_err(|_| CatchAllError::new(format!("port must be an int from 0 to {}", u16::MAX)))?;
+    let interval = Duration::from_millis(parse_arg("interval")?);
+    let players_needed = parse_arg("players")? as usize;
+
+    let mut draws = draws.to_vec();
+    if serve_matches.is_present("seed") {
+        Rng::new(parse_arg("seed")?).shuffle(&mut draws);
+    }
+
+    let io_err = |err: io::Error| CatchAllError::new(format!("io error in bingo server: {}", err));
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(io_err)?;
+    listener.set_nonblocking(true).map_err(io_err)?;
+    println!("serving bingo on {} with {} boards; waiting for {} players",
+             listener.local_addr().map_err(io_err)?, boards.len(), players_needed);
+
+    let lobby = Mutex::new(Lobby { players: Vec::new(), connections: Vec::new(), next_board: 0, drawn: Vec::new(), started: false, over: false });
+    let settings = Settings { boards, win_rules, players_needed };
+
+    let (listener, lobby, settings) = (&listener, &lobby, &settings);
+    thread::scope(|scope| {
+        scope.spawn(move || accept_loop(listener, lobby, settings, scope));
+        run_game(&draws, lobby, settings, interval);
+    });
+    Ok(())
+}
+
+fn accept_loop<'scope, 'env>(listener: &'env TcpListener, lobby: &'env Mutex<Lobby>, settings: &'env Settings,
+                             scope: &'scope thread::Scope<'scope, 'env>)
+{
+    while !lobby.lock().unwrap().over {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                debug!("connection from {}", addr);
+                let mut l = lobby.lock().unwrap();
+                if l.over {
+                    let _ = stream.shutdown(Shutdown::Both);
+                    break;
+                }
+                let ready = stream.set_nonblocking(false)
+                    .and_then(|()| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+                match (ready, stream.try_clone()) {
+                    (Ok(()), Ok(clone)) => {
+                        l.connections.push(clone);
+                        scope.spawn(move || handle_client(stream, lobby, settings));
+                    },
+                    _ => debug!("dropping connection from {}", addr)
+                }
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+            Err(err) => debug!("accept failed: {}", err)
+        }
+    }
+}
+
+fn run_game(draws: &[i32], lobby: &Mutex<Lobby>, settings: &Settings, interval: Duration) {
+    loop {
+        let mut l = lobby.lock().unwrap();
+        if l.players.len() >= settings.players_needed {
+            l.started = true;
+            l.broadcast("START");
+            break;
+        }
+        drop(l);
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    for (turn, &draw) in draws.iter().enumerate() {
+        thread::sleep(interval);
+        let mut l = lobby.lock().unwrap();
+        if l.players.is_empty() || l.players.iter().all(|p| p.won) {
+            break;
+        }
+        l.drawn.push(draw);
+        l.broadcast(&format!("DRAW {} {}", turn + 1, draw));
+    }
+
+    let mut l = lobby.lock().unwrap();
+    l.over = true;
+    l.broadcast("GAMEOVER");
+    // wakes up the handlers blocked on reads so the server can exit,
+    // including those of clients that never joined
+    for stream in &l.connections {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn handle_client(stream: TcpStream, lobby: &Mutex<Lobby>, settings: &Settings) {
+    let reader = match stream.try_clone() {
+        Ok(s) => BufReader::new(s),
+        Err(_) => return
+    };
+    let reply = |msg: &str| {
+        let _ = writeln!(&stream, "{}", msg);
+    };
+    let mut name: Option<String> = None;
+
+    for lr in reader.lines() {
+        let line = match lr {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        let mut words = line.split_whitespace();
+        let mut l = lobby.lock().unwrap();
+        if l.over {
+            break;
+        }
+
+        match (words.next(), &name) {
+            (Some("JOIN"), None) => {
+                let requested = words.collect::<Vec<_>>().join(" ");
+                match join(&mut l, settings, &stream, &requested) {
+                    Ok(player_name) => name = Some(player_name),
+                    Err(err) => reply(&format!("ERROR {}", err))
+                }
+            },
+            (Some("JOIN"), Some(_)) => reply("ERROR already joined"),
+            (Some("BINGO"), Some(player_name)) => claim(&mut l, settings, player_name),
+            (Some("QUIT"), _) => break,
+            (_, None) => reply("ERROR send JOIN <name> first"),
+            (_, Some(_)) => reply("ERROR expected BINGO or QUIT")
+        }
+    }
+
+    let mut l = lobby.lock().unwrap();
+    if let Some(player_name) = name {
+        l.players.retain(|p| p.name != player_name);
+    }
+    if let Ok(addr) = stream.peer_addr() {
+        l.connections.retain(|c| c.peer_addr().ok() != Some(addr));
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+// hands out the next board, written in the same format as the day 4 input
+fn join(l: &mut Lobby, settings: &Settings, stream: &TcpStream, requested: &str) -> Result<String,CatchAllError> {
+    if requested.is_empty() {
+        return Err(CatchAllError::new("JOIN needs a name".to_string()));
+    }
+    if l.started {
+        return Err(CatchAllError::new("game in progress".to_string()));
+    }
+    if l.players.iter().any(|p| p.name == requested) {
+        return Err(CatchAllError::new(format!("{} is taken", requested)));
+    }
+    if l.next_board >= settings.boards.len() {
+        return Err(CatchAllError::new("no boards left".to_string()));
+    }
+
+    let board_idx = l.next_board;
+    let mut msg = format!("BOARD {}\n", board_idx);
+    for row in &settings.boards[board_idx] {
+        let nums: Vec<String> = row.iter().map(|n| format!("{:>2}", n)).collect();
+        msg.push_str(&nums.join(" "));
+        msg.push('\n');
+    }
+    writeln!(&*stream, "{}", msg)
+        .map_err(|err| CatchAllError::new(format!("could not send board: {}", err)))?;
+
+    let player_stream = stream.try_clone()
+        .map_err(|err| CatchAllError::new(format!("could not clone stream: {}", err)))?;
+    l.next_board += 1;
+    l.players.push(Player { name: requested.to_string(), board_idx, stream: player_stream, won: false });
+    let joined = format!("WAITING {}/{}", l.players.len(), settings.players_needed);
+    l.broadcast(&joined);
+    Ok(requested.to_string())
+}
+
+// replays the draws so far on the player's board to check the claim. like the
+// day 4 engine, marking stops at the winning draw, which is what gets scored,
+// however late the claim comes in.
+fn claim(l: &mut Lobby, settings: &Settings, player_name: &str) {
+    let idx = match l.players.iter().position(|p| p.name == player_name) {
+        Some(idx) => idx,
+        None => return
+    };
+    let reject = |l: &Lobby, reason: &str| {
+        let _ = writeln!(&l.players[idx].stream, "REJECT {}", reason);
+    };
+    if l.players[idx].won {
+        return reject(l, "already won");
+    }
+
+    let board = &settings.boards[l.players[idx].board_idx];
+    let mut counts = match BoardCounts::new(board, settings.win_rules) {
+        Ok(counts) => counts,
+        Err(err) => return reject(l, &err.to_string())
+    };
+    let positions = board_positions(board);
+    let mut winning_draw = None;
+    for &draw in &l.drawn {
+        if let Some(&pos) = positions.get(&draw) {
+            counts.track_draw(pos);
+            if counts.is_win() {
+                winning_draw = Some(draw);
+                break;
+            }
+        }
+    }
+
+    let win = counts.winning_line().map(|line| line.name.clone());
+    match (win, winning_draw) {
+        (Some(line), Some(winning_draw)) => {
+            let score = counts.compute_score(board) as i64 * winning_draw as i64;
+            l.players[idx].won = true;
+            l.broadcast(&format!("WIN {} {} {}", player_name, score, line));
+        },
+        _ => reject(l, "no winning line yet")
+    }
+}
@@ -1,33 +1,207 @@
 use std::io::BufReader;
 use std::io::Read;
+use std::fs;
 use std::fs::File;
+use std::fmt;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 
 use log::debug;
 
+use clap::{Arg, ArgMatches};
+
 use crate::util::error::CatchAllError;
 
-pub fn run(mut input_reader: BufReader<File>) {
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("overlap_method")
+            .long("overlap-method")
+            .takes_value(true)
+            .possible_values(&["raster", "analytic", "check"])
+            .default_value("raster")
+            .help("Day 5: counts overlaps by rasterising lines onto a grid, by intersecting \
+                   segments directly, or both, checking that they agree"),
+        Arg::with_name("slopes")
+            .long("slopes")
+            .takes_value(true)
+            .possible_values(&["45", "any"])
+            .default_value("45")
+            .help("Day 5: whether lines at slopes other than 45 degrees are rejected or included"),
+        Arg::with_name("heatmap")
+            .long("heatmap")
+            .takes_value(true)
+            .value_name("PREFIX")
+            .help("Day 5: writes PREFIX-orthogonal.ppm and PREFIX-diagonal.ppm heatmaps of the vent grid"),
+        Arg::with_name("ascii")
+            .long("ascii")
+            .help("Day 5: prints the vent grid with and without diagonals"),
+        Arg::with_name("crop")
+            .long("crop")
+            .takes_value(true)
+            .value_name("X0,Y0,X1,Y1")
+            .allow_hyphen_values(true)
+            .help("Day 5: limits --heatmap and --ascii to this inclusive window"),
+    ]
+}
+
+pub fn query_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("at")
+            .long("at")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("X,Y")
+            .allow_hyphen_values(true)
+            .help("Lists the lines covering this point"),
+        Arg::with_name("max")
+            .long("max")
+            .help("Reports the maximum overlap and the points that have it"),
+        Arg::with_name("at_least")
+            .long("at-least")
+            .takes_value(true)
+            .value_name("K")
+            .help("Counts the points covered by at least K lines"),
+        Arg::with_name("pairs")
+            .long("pairs")
+            .help("Lists every pair of lines that share a point"),
+        Arg::with_name("orthogonal")
+            .long("orthogonal")
+            .help("Only considers horizontal and vertical lines, as in part A"),
+    ]
+}
+
+pub fn run(input_reader: BufReader<File>, matches: &ArgMatches) {
+    let parsed_lines = read_lines(input_reader, matches).expect("couldn't parse input");
+
+    let method = Method::from_arg(matches.value_of("overlap_method"));
+
+    let result_a = day_5a(&parsed_lines, method).expect("error during part a");
+    println!("Day 5; Part A: {}", result_a);
+
+    let result_b = day_5b(&parsed_lines, method).expect("error during part b");
+    println!("Day 5; Part B: {}", result_b);
+
+    if matches.is_present("heatmap") || matches.is_present("ascii") {
+        let crop = matches.value_of("crop")
+            .map(|c| parse_crop(c).expect("invalid crop window"));
+        for (variant, include_diag) in &[("orthogonal", false), ("diagonal", true)] {
+            let included = included_lines(&parsed_lines, *include_diag);
+            let grid = raster(&included);
+            let window = match crop.or(grid.bounds) {
+                Some(window) => window,
+                None => continue
+            };
+
+            if matches.is_present("ascii") {
+                println!("Day 5; {} grid:\n{}", variant, render::ascii(&grid, window));
+            }
+            if let Some(prefix) = matches.value_of("heatmap") {
+                let filename = format!("{}-{}.ppm", prefix, variant);
+                let image = render::ppm(&grid, window).expect("could not render heatmap");
+                fs::write(&filename, image).expect("could not write heatmap");
+                println!("Day 5; wrote {} heatmap to {}", variant, filename);
+            }
+        }
+    }
+}
+
+// comma separated ints, e.g. for --crop and --at
+fn parse_ints(s: &str) -> Option<Vec<i64>> {
+    s.split(',')
+        .map(|n| n.trim().parse::<i64>())
+        .collect::<Result<_,_>>()
+        .ok()
+}
+
+fn parse_crop(crop: &str) -> Result<Window,CatchAllError> {
+    let nums = parse_ints(crop)
+        .ok_or_else(|| CatchAllError::new(format!("crop {} should be x0,y0,x1,y1", crop)))?;
+    match nums[..] {
+        [x0, y0, x1, y1] if x0 <= x1 && y0 <= y1 =>
+            Ok(Window { min: Point { x: x0, y: y0 }, max: Point { x: x1, y: y1 } }),
+        _ => Err(CatchAllError::new(format!("crop {} should be x0,y0,x1,y1 with x0<=x1 and y0<=y1", crop)))
+    }
+}
+
+fn read_lines(mut input_reader: BufReader<File>, matches: &ArgMatches) -> Result<Vec<Line>,CatchAllError> {
     let mut input_str = String::new();
     input_reader.read_to_string(&mut input_str)
-        .expect("could not read input");
+        .map_err(|err| CatchAllError::new(format!("could not read input: {}", err)))?;
 
 	let (_, parsed_lines) = parse::parse_lines(&input_str)
-        .expect("couldn't parse input");
+        .map_err(|err| CatchAllError::new(format!("couldn't parse input: {}", err)))?;
 
     debug!("\n{:?}", parsed_lines);
 
-    let result_a = day_5a(&parsed_lines).expect("error during part a");
-    println!("Day 5; Part A: {}", result_a);
+    if matches.value_of("slopes") != Some("any") {
+        check_slopes(&parsed_lines)?;
+    }
 
-    let result_b = day_5b(&parsed_lines).expect("error during part b");
-    println!("Day 5; Part B: {}", result_b);
+    Ok(parsed_lines)
+}
+
+pub fn query_vents(input_reader: BufReader<File>, matches: &ArgMatches, query_matches: &ArgMatches) ->
+    Result<(),CatchAllError>
+{
+    let parsed_lines = read_lines(input_reader, matches)?;
+    let field = VentField::new(&parsed_lines, !query_matches.is_present("orthogonal"));
+    let describe = |idx: usize| format!("line {} ({})", idx + 1, line_str(parsed_lines[idx]));
+
+    for at in query_matches.values_of("at").into_iter().flatten() {
+        let point = match parse_ints(at).as_deref() {
+            Some(&[x, y]) => Point { x, y },
+            _ => return Err(CatchAllError::new(format!("point {} should be x,y", at)))
+        };
+        let covering = field.lines_at(point);
+        println!("{} is covered by {} lines", point, covering.len());
+        for &idx in covering {
+            println!("  {}", describe(idx));
+        }
+    }
+
+    if query_matches.is_present("max") {
+        let (max, points) = field.max_overlap();
+        let points: Vec<String> = points.iter().map(|p| p.to_string()).collect();
+        println!("max overlap is {}, at {}", max, points.join(" "));
+    }
+
+    if let Some(k) = query_matches.value_of("at_least") {
+        // every point is covered by at least 0 lines, so that isn't a useful question
+        let k = k.parse::<usize>().ok().filter(|&k| k > 0)
+            .ok_or_else(|| CatchAllError::new("at-least must be a positive int".to_string()))?;
+        println!("{} points are covered by at least {} lines", field.count_at_least(k), k);
+    }
+
+    if query_matches.is_present("pairs") {
+        let pairs = field.intersecting_pairs();
+        println!("{} pairs of lines intersect", pairs.len());
+        for (a, b) in pairs {
+            println!("  {} and {}", describe(a), describe(b));
+        }
+    }
+
+    Ok(())
+}
+
+// a point as given in the input: x grows to the right and y downwards. the
+// grid takes care of mapping these onto rows and columns.
+#[derive(Copy,Clone,Debug,PartialEq,Eq,Hash)]
+struct Point {
+    x: i64,
+    y: i64
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
 }
 
-type Coord = (usize,usize);
-type Line = (Coord, Coord);
+type Line = (Point, Point);
 
 mod parse {
-    use super::Coord;
+    use super::Point;
     use super::Line;
 
     use nom::{
@@ -49,99 +223,582 @@ mod parse {
     };
 
     use crate::util::parse::{
-        usize_parser
+        i64_parser
     };
 
     pub fn parse_lines(input: &str) -> IResult<&str, Vec<Line>, VerboseError<&str>> {
-        fn parse_coord(input: &str) -> IResult<&str, Coord, VerboseError<&str>> {
+        fn parse_point(input: &str) -> IResult<&str, Point, VerboseError<&str>> {
             map(
-                separated_pair(usize_parser, char(','), usize_parser),
-                |(x,y)| (y,x) // input is x,y but grid is vec of rows so indexes are flipped
+                separated_pair(i64_parser, char(','), i64_parser),
+                |(x,y)| Point { x, y }
             )(input)
         }
 
         fn parse_line(input: &str) -> IResult<&str, Line, VerboseError<&str>> {
-            separated_pair(parse_coord, tag(" -> "), parse_coord)(input)
+            separated_pair(parse_point, tag(" -> "), parse_point)(input)
         } 
 
         many1(terminated(parse_line, newline))(input)
     }
 }
 
-fn covered_coords(line: Line) -> Vec<Coord> {
-    fn range(start: usize, end: usize) -> Box<dyn Iterator<Item = usize>> {
-        if start <= end {
-            return Box::new((start..=end).into_iter());
-        } else {
-            return Box::new((end..=start).into_iter().rev());
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// the (x, y) delta between adjacent points of the line, and how many steps it takes
+fn line_step(line: Line) -> ((i64,i64), i64) {
+    let dx = line.1.x - line.0.x;
+    let dy = line.1.y - line.0.y;
+    let steps = gcd(dx.abs(), dy.abs());
+    if steps == 0 {
+        ((0, 0), 0)
+    } else {
+        ((dx / steps, dy / steps), steps)
+    }
+}
+
+// every integer point the line passes exactly through. for lines that aren't
+// horizontal, vertical or 45 degrees that's only every few cells.
+fn covered_points(line: Line) -> Vec<Point> {
+    let ((sx, sy), steps) = line_step(line);
+    let out: Vec<Point> = (0..=steps)
+        .map(|k| Point { x: line.0.x + k * sx, y: line.0.y + k * sy })
+        .collect();
+    debug!("points for line {:?}: {:?}", line, out);
+    out
+}
+
+fn is_diagonal(line: Line) -> bool {
+    line.0.x != line.1.x && line.0.y != line.1.y
+}
+
+fn is_45_degrees(line: Line) -> bool {
+    (line.0.x - line.1.x).abs() == (line.0.y - line.1.y).abs()
+}
+
+// formats a line the way it appears in the input
+fn line_str(line: Line) -> String {
+    format!("{} -> {}", line.0, line.1)
+}
+
+fn check_slopes(lines: &[Line]) -> Result<(),CatchAllError> {
+    match lines.iter().position(|&line| is_diagonal(line) && !is_45_degrees(line)) {
+        Some(idx) => Err(CatchAllError::new(format!(
+                    "line {} ({}) is not horizontal, vertical or 45 degrees; use --slopes any to include it",
+                    idx + 1, line_str(lines[idx])))),
+        None => Ok(())
+    }
+}
+
+
+#[derive(Copy,Clone)]
+enum Method {
+    Raster,
+    Analytic,
+    Check
+}
+
+impl Method {
+    fn from_arg(arg: Option<&str>) -> Method {
+        match arg {
+            Some("analytic") => Method::Analytic,
+            Some("check") => Method::Check,
+            _ => Method::Raster
         }
     }
-    let mut out: Vec<Coord> = Vec::new();
-    if is_diagonal(line) {
-        for (i,j) in range(line.0.0,line.1.0).zip(range(line.0.1,line.1.1)) {
-            out.push((i,j));
+}
+
+fn day_5a(lines: &[Line], method: Method) -> Result<usize,CatchAllError> {
+    both_parts(lines, false, method)
+}
+
+fn day_5b(lines: &[Line], method: Method) -> Result<usize,CatchAllError> {
+    both_parts(lines, true, method)
+}
+
+fn included_lines(lines: &[Line], include_diag: bool) -> Vec<Line> {
+    lines.iter()
+        .filter(|line| include_diag || !is_diagonal(**line))
+        .copied()
+        .collect()
+}
+
+fn both_parts(lines: &[Line], include_diag: bool, method: Method) -> Result<usize, CatchAllError> {
+    let included = included_lines(lines, include_diag);
+
+    match method {
+        Method::Raster => Ok(raster_overlaps(&included)),
+        Method::Analytic => Ok(analytic::count_overlaps(&included)),
+        Method::Check => {
+            let raster = raster_overlaps(&included);
+            let analytic = analytic::count_overlaps(&included);
+            if raster != analytic {
+                return Err(CatchAllError::new(format!(
+                            "raster counted {} overlaps but analytic counted {}", raster, analytic)));
+            }
+            Ok(raster)
         }
-    } else {
-        for i in range(line.0.0,line.1.0) {
-            for j in range(line.0.1,line.1.1) {
-                out.push((i,j));
+    }
+}
+
+fn raster_overlaps(included: &[Line]) -> usize {
+    raster(included).count_overlaps()
+}
+
+fn raster(included: &[Line]) -> Grid {
+    let mut grid = Grid::for_lines(included);
+
+    for line in included {
+        for point in covered_points(*line) {
+            grid.increment(point);
+        }
+        debug!("added line {:?}, grid is:\n{}", line, grid.pretty());
+    }
+
+    debug!("grid after:\n{}", grid.pretty());
+
+    grid
+}
+
+// every point covered by a line, with the indexes of the lines covering it
+struct VentField {
+    cells: HashMap<Point, Vec<usize>>
+}
+
+impl VentField {
+    fn new(lines: &[Line], include_diag: bool) -> VentField {
+        let mut cells: HashMap<Point, Vec<usize>> = HashMap::new();
+        for (idx, &line) in lines.iter().enumerate() {
+            if include_diag || !is_diagonal(line) {
+                for point in covered_points(line) {
+                    cells.entry(point).or_default().push(idx);
+                }
+            }
+        }
+        VentField { cells }
+    }
+
+    fn lines_at(&self, point: Point) -> &[usize] {
+        self.cells.get(&point).map(|v| &v[..]).unwrap_or(&[])
+    }
+
+    // the highest number of lines covering one point, and the points with that
+    // many, in reading order
+    fn max_overlap(&self) -> (usize, Vec<Point>) {
+        let max = self.cells.values().map(|v| v.len()).max().unwrap_or(0);
+        let mut points: Vec<Point> = self.cells.iter()
+            .filter(|(_, v)| v.len() == max)
+            .map(|(&p, _)| p)
+            .collect();
+        points.sort_unstable_by_key(|p| (p.y, p.x));
+        (max, points)
+    }
+
+    fn count_at_least(&self, k: usize) -> usize {
+        self.cells.values().filter(|v| v.len() >= k).count()
+    }
+
+    fn intersecting_pairs(&self) -> Vec<(usize,usize)> {
+        let mut pairs: BTreeSet<(usize,usize)> = BTreeSet::new();
+        for covering in self.cells.values() {
+            for (n, &a) in covering.iter().enumerate() {
+                for &b in &covering[n + 1..] {
+                    pairs.insert((a.min(b), a.max(b)));
+                }
             }
         }
+        pairs.into_iter().collect()
     }
-    debug!("coords for line {:?}: {:?}", line, out);
-    return out;
 }
 
-fn is_diagonal(line: Line) -> bool {
-    line.0.0 != line.1.0 && line.0.1 != line.1.1
+// a dense grid covering the lines' bounding box is used unless the lines cover
+// only a small fraction of it, in which case only the touched cells are stored
+const SPARSE_RATIO: usize = 16;
+
+// an inclusive rectangle of points
+#[derive(Copy,Clone,Debug)]
+struct Window {
+    min: Point,
+    max: Point
 }
 
+impl Window {
+    fn height(&self) -> usize {
+        (self.max.y - self.min.y + 1) as usize
+    }
 
-fn day_5a(lines: &[Line]) -> Result<usize,CatchAllError> {
-    both_parts(lines, false)
+    fn width(&self) -> usize {
+        (self.max.x - self.min.x + 1) as usize
+    }
+
+    fn contains(&self, point: Point) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+}
+
+// counts per point. dense cells are stored row by row (y major) from the
+// bounding box's top left corner, so points anywhere, including negative ones,
+// map onto it.
+struct Grid {
+    bounds: Option<Window>, // None when there are no lines
+    cells: Cells
 }
 
-fn day_5b(lines: &[Line]) -> Result<usize,CatchAllError> {
-    both_parts(lines, true)
+enum Cells {
+    Dense(Vec<i32>),
+    Sparse(HashMap<Point, i32>)
 }
 
-fn both_parts(lines: &[Line], include_diag: bool) -> Result<usize, CatchAllError> {
-    let mut grid: Vec<Vec<i32>> = Vec::new();
-    let grid_size = 999; // input coords are <=3 digits
-    //let grid_size = 10; // example coords are 1 digit
-    for _ in 0..grid_size {
-        let mut row: Vec<i32> = Vec::new();
-        for _ in 0..grid_size {
-            row.push(0);
+impl Grid {
+    fn for_lines(lines: &[Line]) -> Grid {
+        let points = || lines.iter().flat_map(|line| vec![line.0, line.1]);
+        let (min_x, max_x) = (points().map(|p| p.x).min(), points().map(|p| p.x).max());
+        let (min_y, max_y) = (points().map(|p| p.y).min(), points().map(|p| p.y).max());
+
+        let bounds = match (min_x, max_x, min_y, max_y) {
+            (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) =>
+                Some(Window { min: Point { x: min_x, y: min_y }, max: Point { x: max_x, y: max_y } }),
+            _ => None
+        };
+
+        let covered: usize = lines.iter().map(|&line| line_len(line)).sum();
+        let area = bounds.map(|b| b.height().saturating_mul(b.width())).unwrap_or(0);
+
+        if area / SPARSE_RATIO > covered {
+            debug!("using sparse grid: bounding box area {} vs {} covered cells", area, covered);
+            Grid { bounds, cells: Cells::Sparse(HashMap::with_capacity(covered)) }
+        } else {
+            debug!("using dense grid over {:?}", bounds);
+            Grid { bounds, cells: Cells::Dense(vec![0; area]) }
+        }
+    }
+
+    fn dense_idx(bounds: Window, point: Point) -> usize {
+        (point.y - bounds.min.y) as usize * bounds.width() + (point.x - bounds.min.x) as usize
+    }
+
+    fn increment(&mut self, point: Point) {
+        match (&mut self.cells, self.bounds) {
+            (Cells::Dense(cells), Some(bounds)) => cells[Grid::dense_idx(bounds, point)] += 1,
+            (Cells::Sparse(cells), _) => *cells.entry(point).or_insert(0) += 1,
+            (Cells::Dense(_), None) => panic!("incremented a cell of an empty grid")
+        }
+    }
+
+    fn get(&self, point: Point) -> i32 {
+        match (&self.cells, self.bounds) {
+            (Cells::Dense(cells), Some(bounds)) if bounds.contains(point) =>
+                cells[Grid::dense_idx(bounds, point)],
+            (Cells::Sparse(cells), _) => cells.get(&point).copied().unwrap_or(0),
+            _ => 0
         }
-        grid.push(row);
     }
 
-    for line in lines {
-        if include_diag || !is_diagonal(*line) {
-            for (i,j) in covered_coords(*line) {
-                grid[i][j] = grid[i][j] + 1;
+    fn count_overlaps(&self) -> usize {
+        match &self.cells {
+            Cells::Dense(cells) => cells.iter().filter(|&&c| c > 1).count(),
+            Cells::Sparse(cells) => cells.values().filter(|&&c| c > 1).count()
+        }
+    }
+
+    fn pretty(&self) -> String {
+        match (&self.cells, self.bounds) {
+            (Cells::Dense(_), Some(bounds)) => render::ascii(self, bounds),
+            (Cells::Sparse(cells), _) => format!("(sparse grid with {} touched cells)\n", cells.len()),
+            (Cells::Dense(_), None) => String::from("(empty grid)\n")
+        }
+    }
+}
+
+// number of cells a line covers
+fn line_len(line: Line) -> usize {
+    line_step(line).1 as usize + 1
+}
+
+mod render {
+    use super::Grid;
+    use super::Point;
+    use super::Window;
+
+    use crate::util::error::CatchAllError;
+
+    // largest image we'll write, to avoid accidentally rendering a huge sparse grid
+    const MAX_PIXELS: usize = 1 << 24;
+
+    // one character per cell: '.' for none, the count up to 9, '*' beyond that
+    pub fn ascii(grid: &Grid, window: Window) -> String {
+        let mut out = String::with_capacity((window.width() + 1) * window.height());
+        for y in window.min.y..=window.max.y {
+            for x in window.min.x..=window.max.x {
+                out.push(match grid.get(Point { x, y }) {
+                    0 => '.',
+                    c @ 1..=9 => (b'0' + c as u8) as char,
+                    _ => '*'
+                });
             }
-            debug!("added line {:?}, grid is:\n{}", line, pretty_grid(&grid));
+            out.push('\n');
+        }
+        out
+    }
+
+    // black for no vents, then dark red through yellow to white at the
+    // window's busiest cell
+    fn heat_color(count: i32, max: i32) -> [u8; 3] {
+        if count <= 0 || max <= 0 {
+            return [0, 0, 0];
         }
+        let t = 0.25 + 0.75 * count as f64 / max as f64;
+        let channel = |offset: f64| ((3.0 * t - offset).clamp(0.0, 1.0) * 255.0) as u8;
+        [channel(0.0), channel(1.0), channel(2.0)]
     }
 
-    debug!("grid after:\n{}", pretty_grid(&grid));
+    // a binary ppm (P6) image with one pixel per cell
+    pub fn ppm(grid: &Grid, window: Window) -> Result<Vec<u8>,CatchAllError> {
+        let pixels = window.height().saturating_mul(window.width());
+        if pixels > MAX_PIXELS {
+            return Err(CatchAllError::new(format!(
+                        "{}x{} heatmap is too large; use --crop to pick a smaller window",
+                        window.width(), window.height())));
+        }
+
+        let mut counts = Vec::with_capacity(pixels);
+        for y in window.min.y..=window.max.y {
+            for x in window.min.x..=window.max.x {
+                counts.push(grid.get(Point { x, y }));
+            }
+        }
+        let max = counts.iter().copied().max().unwrap_or(0);
 
-    return Ok(grid.iter()
-            .flat_map(|r| r.iter())
-            .filter(|&cell_count| cell_count > &1)
-            .count());
+        let mut out = format!("P6\n{} {}\n255\n", window.width(), window.height()).into_bytes();
+        for count in counts {
+            out.extend_from_slice(&heat_color(count, max));
+        }
+        Ok(out)
+    }
 }
 
-fn pretty_grid(grid: &[Vec<i32>]) -> String {
-    let mut out: String = String::from("");
-    for row in grid {
-        for cell in row {
-            out.push_str(&cell.to_string());
+// counts overlapping cells without a grid. segments on the same infinite line
+// are merged as intervals along it, and segments on different lines meet in at
+// most one cell, so the only cells to look at individually are crossings.
+mod analytic {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use super::Line;
+    use super::gcd;
+
+    // the infinite line through a segment: the points p where
+    // dir.1 * p.0 - dir.0 * p.1 == offset. dir is the step between adjacent
+    // cells on the line, reduced and with a canonical sign.
+    #[derive(PartialEq,Eq,Hash,Clone,Copy,Debug)]
+    struct Family {
+        dir: (i64,i64),
+        offset: i64
+    }
+
+    impl Family {
+        // position along the line, in multiples of step()
+        fn param(&self, p: (i64,i64)) -> i64 {
+            p.0 * self.dir.0 + p.1 * self.dir.1
         }
-        out.push('\n')
+
+        fn step(&self) -> i64 {
+            self.dir.0 * self.dir.0 + self.dir.1 * self.dir.1
+        }
+    }
+
+    struct Segment {
+        family: Family,
+        start: i64,
+        end: i64
+    }
+
+    fn segment(line: Line) -> Segment {
+        let p0 = (line.0.x, line.0.y);
+        let p1 = (line.1.x, line.1.y);
+        let (d0, d1) = (p1.0 - p0.0, p1.1 - p0.1);
+        let g = gcd(d0.abs(), d1.abs());
+        // a single point can be treated as lying on its vertical line
+        let mut dir = if g == 0 { (0, 1) } else { (d0 / g, d1 / g) };
+        if dir.0 < 0 || (dir.0 == 0 && dir.1 < 0) {
+            dir = (-dir.0, -dir.1);
+        }
+        let family = Family { dir, offset: dir.1 * p0.0 - dir.0 * p0.1 };
+        let (t0, t1) = (family.param(p0), family.param(p1));
+        Segment { family, start: t0.min(t1), end: t0.max(t1) }
     }
 
-    return out;
+    // the half-open parameter ranges covered by at least two of the segments,
+    // which all lie on the same family
+    fn overlap_ranges(family: Family, segments: &[&Segment]) -> Vec<(i64,i64)> {
+        let mut events: Vec<(i64,i32)> = segments.iter()
+            .flat_map(|s| vec![(s.start, 1), (s.end + family.step(), -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut ranges: Vec<(i64,i64)> = Vec::new();
+        let mut coverage = 0;
+        for (k, &(pos, delta)) in events.iter().enumerate() {
+            coverage += delta;
+            let next_pos = match events.get(k + 1) {
+                Some(&(next_pos, _)) => next_pos,
+                None => break
+            };
+            if coverage >= 2 && next_pos > pos {
+                match ranges.last_mut() {
+                    Some(last) if last.1 == pos => last.1 = next_pos,
+                    _ => ranges.push((pos, next_pos))
+                }
+            }
+        }
+        ranges
+    }
+
+    fn in_ranges(ranges: &[(i64,i64)], t: i64) -> bool {
+        let idx = ranges.partition_point(|r| r.1 <= t);
+        idx < ranges.len() && ranges[idx].0 <= t
+    }
+
+    // where two segments on different families cross, if it's a cell on both
+    fn crossing(a: &Segment, b: &Segment) -> Option<(i64,i64)> {
+        let (fa, fb) = (a.family, b.family);
+        let det = (fa.dir.0 * fb.dir.1 - fb.dir.0 * fa.dir.1) as i128;
+        if det == 0 {
+            return None;
+        }
+        let u = fa.dir.0 as i128 * fb.offset as i128 - fb.dir.0 as i128 * fa.offset as i128;
+        let v = fa.dir.1 as i128 * fb.offset as i128 - fb.dir.1 as i128 * fa.offset as i128;
+        if u % det != 0 || v % det != 0 {
+            return None;
+        }
+        let p = ((u / det) as i64, (v / det) as i64);
+        let (ta, tb) = (fa.param(p), fb.param(p));
+        if ta < a.start || ta > a.end || tb < b.start || tb > b.end {
+            return None;
+        }
+        Some(p)
+    }
+
+    pub fn count_overlaps(lines: &[Line]) -> usize {
+        let segments: Vec<Segment> = lines.iter().map(|&line| segment(line)).collect();
+
+        let mut families: HashMap<Family, Vec<&Segment>> = HashMap::new();
+        for s in &segments {
+            families.entry(s.family).or_default().push(s);
+        }
+        let overlaps: HashMap<Family, Vec<(i64,i64)>> = families.iter()
+            .map(|(&family, members)| (family, overlap_ranges(family, members)))
+            .collect();
+
+        let mut total: i64 = overlaps.iter()
+            .flat_map(|(family, ranges)| ranges.iter().map(move |r| (r.1 - r.0) / family.step()))
+            .sum();
+
+        // the families meeting at each crossing cell
+        let mut crossings: HashMap<(i64,i64), HashSet<Family>> = HashMap::new();
+        for (i, a) in segments.iter().enumerate() {
+            for b in &segments[i + 1..] {
+                if let Some(p) = crossing(a, b) {
+                    let meeting = crossings.entry(p).or_default();
+                    meeting.insert(a.family);
+                    meeting.insert(b.family);
+                }
+            }
+        }
+
+        // a crossing cell not already in an overlap range is new; one inside
+        // the overlap ranges of several families has been counted once per family
+        for (p, meeting) in &crossings {
+            let counted = meeting.iter()
+                .filter(|family| in_ranges(&overlaps[family], family.param(*p)))
+                .count() as i64;
+            total += match counted {
+                0 => 1,
+                n => 1 - n
+            };
+        }
+
+        total as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::Rng;
+
+    fn line(x0: i64, y0: i64, x1: i64, y1: i64) -> Line {
+        (Point { x: x0, y: y0 }, Point { x: x1, y: y1 })
+    }
+
+    // checks the analytic count against the raster and gives the count they agree on
+    fn overlaps(lines: &[Line]) -> usize {
+        let raster = raster_overlaps(lines);
+        assert_eq!(analytic::count_overlaps(lines), raster, "analytic for {:?}", lines);
+        raster
+    }
+
+    #[test]
+    fn example() {
+        let (_, lines) = parse::parse_lines(&fs::read_to_string("examples/day_5.txt").unwrap()).unwrap();
+        assert_eq!(overlaps(&included_lines(&lines, false)), 5);
+        assert_eq!(overlaps(&included_lines(&lines, true)), 12);
+    }
+
+    #[test]
+    fn single_points() {
+        assert_eq!(overlaps(&[line(1, 1, 1, 1), line(1, 1, 1, 1)]), 1);
+        assert_eq!(overlaps(&[line(1, 1, 1, 1), line(2, 2, 2, 2)]), 0);
+        assert_eq!(overlaps(&[line(2, 1, 2, 1), line(0, 1, 4, 1)]), 1);
+        assert_eq!(overlaps(&[line(2, 1, 2, 1), line(2, 0, 2, 4)]), 1);
+        assert_eq!(overlaps(&[line(2, 1, 2, 1), line(0, 0, 4, 2)]), 1);
+        // between the cells a steep line passes through
+        assert_eq!(overlaps(&[line(1, 0, 1, 0), line(0, 0, 4, 2)]), 0);
+    }
+
+    #[test]
+    fn collinear_overlaps() {
+        assert_eq!(overlaps(&[line(0, 0, 5, 0), line(8, 0, 3, 0), line(4, 0, 10, 0)]), 6);
+        assert_eq!(overlaps(&[line(0, 0, 4, 4), line(2, 2, 6, 6)]), 3);
+        assert_eq!(overlaps(&[line(0, 0, 6, 3), line(2, 1, 8, 4)]), 3);
+        // touching end to end, and parallel but on different lines
+        assert_eq!(overlaps(&[line(0, 0, 2, 0), line(2, 0, 4, 0)]), 1);
+        assert_eq!(overlaps(&[line(0, 0, 4, 0), line(0, 1, 4, 1)]), 0);
+    }
+
+    #[test]
+    fn crossings() {
+        let star = [line(0, 2, 4, 2), line(2, 0, 2, 4), line(0, 0, 4, 4), line(0, 4, 4, 0)];
+        assert_eq!(overlaps(&star), 1);
+
+        // the crossing is also inside a collinear overlap on one or more of the lines
+        let mut lines = star.to_vec();
+        lines.push(line(1, 2, 3, 2));
+        assert_eq!(overlaps(&lines), 3);
+        lines.push(line(1, 1, 3, 3));
+        assert_eq!(overlaps(&lines), 5);
+    }
+
+    #[test]
+    fn other_slopes() {
+        assert_eq!(overlaps(&[line(0, 0, 4, 2), line(2, 0, 2, 4), line(0, 1, 4, 1)]), 1);
+        // crossing between cells
+        assert_eq!(overlaps(&[line(0, 0, 3, 1), line(1, 0, 1, 3)]), 0);
+        assert_eq!(overlaps(&[line(0, 0, 3, 6), line(0, 6, 3, 0)]), 0);
+        assert_eq!(overlaps(&[line(-3, -1, 3, 1), line(-1, 3, 1, -3)]), 1);
+    }
+
+    #[test]
+    fn random_lines() {
+        let mut rng = Rng::new(5);
+        for _ in 0..500 {
+            // a small box makes coincident points, shared lines and crossings common
+            let size = [2, 4, 8][rng.below(3) as usize];
+            let mut coord = || rng.below(2 * size + 1) as i64 - size as i64;
+            let n = 1 + coord().unsigned_abs() as usize + coord().unsigned_abs() as usize;
+            let lines: Vec<Line> = (0..n).map(|_| line(coord(), coord(), coord(), coord())).collect();
+            overlaps(&lines);
+        }
+    }
 }
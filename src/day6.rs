@@ -1,27 +1,250 @@
+use std::fs;
 use std::io::BufReader;
 use std::io::Read;
 use std::fs::File;
 use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::fmt;
 
+use clap::{Arg, ArgMatches};
 use log::debug;
 
+use crate::util::bignum::BigUint;
 use crate::util::error::CatchAllError;
 
-pub fn run(mut input_reader: BufReader<File>) {
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("fish_method")
+            .long("fish-method")
+            .takes_value(true)
+            .possible_values(&["step", "matrix"])
+            .default_value("step")
+            .help("Day 6: simulates a day at a time, or raises the daily transition matrix to the day count"),
+        Arg::with_name("fish_counter")
+            .long("fish-counter")
+            .takes_value(true)
+            .possible_values(&["i64", "big"])
+            .default_value("i64")
+            .help("Day 6: counter type for the step method; i64 reports an error on overflow, big never overflows"),
+        Arg::with_name("fish_days_a")
+            .long("fish-days-a")
+            .takes_value(true)
+            .value_name("DAYS")
+            .default_value("80")
+            .help("Day 6: days to simulate for part A"),
+        Arg::with_name("fish_days_b")
+            .long("fish-days-b")
+            .takes_value(true)
+            .value_name("DAYS")
+            .default_value("256")
+            .help("Day 6: days to simulate for part B"),
+        Arg::with_name("fish_days")
+            .long("fish-days")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("DAYS")
+            .help("Day 6: also counts the fish after this many days"),
+        Arg::with_name("fish_modulus")
+            .long("fish-modulus")
+            .takes_value(true)
+            .value_name("P")
+            .help("Day 6: reports matrix method counts modulo P, usually a large prime, instead of exactly"),
+        Arg::with_name("fish_history")
+            .long("fish-history")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Day 6: writes each day's timer counts, up to the longest day count, to a CSV file"),
+        Arg::with_name("fish_growth")
+            .long("fish-growth")
+            .help("Day 6: compares each species' daily growth with its long run rate"),
+        Arg::with_name("fish_cycle")
+            .long("fish-cycle")
+            .takes_value(true)
+            .value_name("DAYS")
+            .default_value("7")
+            .help("Day 6: days between a fish's spawns, when no --species is given"),
+        Arg::with_name("fish_new_cycle")
+            .long("fish-new-cycle")
+            .takes_value(true)
+            .value_name("DAYS")
+            .default_value("9")
+            .help("Day 6: days until a new fish first spawns, when no --species is given"),
+        Arg::with_name("species")
+            .long("species")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("NAME:CYCLE:NEW[:MORTALITY]")
+            .help("Day 6: adds a species, where MORTALITY is the percentage dying each time they spawn. \
+                   line n of the input holds the timers of the nth species"),
+    ]
+}
+
+pub fn run(mut input_reader: BufReader<File>, matches: &ArgMatches) {
     let mut input_str = String::new();
     input_reader.read_to_string(&mut input_str)
         .expect("could not read input");
 
-	let (_, init_fish) = parse::parse(&input_str)
-        .expect("couldn't parse input");
+    let species = species_from(matches).expect("invalid species");
+    let init_fish = parse_fish(&input_str, &species).expect("couldn't parse input");
+
+    debug!("species: {:?}, init_fish: {:?}", species, init_fish);
+
+    let days_arg = |days: &str| days.parse::<u64>()
+        .unwrap_or_else(|_| panic!("day count {} must be a non-negative int", days));
+    let mut queries = vec![
+        ("Part A".to_string(), days_arg(matches.value_of("fish_days_a").unwrap_or_default())),
+        ("Part B".to_string(), days_arg(matches.value_of("fish_days_b").unwrap_or_default())),
+    ];
+    for days in matches.values_of("fish_days").into_iter().flatten() {
+        queries.push((format!("after {} days", days), days_arg(days)));
+    }
+
+    if matches.value_of("fish_method") == Some("matrix") {
+        match matches.value_of("fish_modulus").map(|m| parse_modulus(m).expect("invalid modulus")) {
+            Some(p) => report_matrix(&species, &init_fish, &queries, |n| matrix::Modular::new(n, p), &format!(" (mod {})", p)),
+            None => report_matrix(&species, &init_fish, &queries, BigUint::from_u64, "")
+        }
+    } else if matches.value_of("fish_counter") == Some("big") {
+        report_step::<BigUint>(&species, &init_fish, &queries);
+    } else {
+        report_step::<i64>(&species, &init_fish, &queries);
+    }
+
+    if matches.is_present("fish_history") || matches.is_present("fish_growth") {
+        let max_days = queries.iter().map(|q| q.1).max().unwrap_or(0);
+        let history = if matches.value_of("fish_counter") == Some("big") {
+            history_totals::<BigUint>(&species, &init_fish, max_days, matches.value_of("fish_history"))
+        } else {
+            history_totals::<i64>(&species, &init_fish, max_days, matches.value_of("fish_history"))
+        }.expect("error recording history");
+
+        if matches.is_present("fish_growth") {
+            for (s, totals) in species.iter().zip(&history) {
+                report_growth(s, totals);
+            }
+        }
+    }
+}
+
+// one kind of fish and its life cycle
+#[derive(Clone,Debug)]
+struct Species {
+    name: String,
+    cycle_len: usize,
+    new_cycle_len: usize,
+    mortality: u64 // percent of the spawning fish that die afterwards, with survivors rounded down
+}
+
+impl Species {
+    // the number of distinct timer values a fish can have
+    fn timers(&self) -> usize {
+        self.cycle_len.max(self.new_cycle_len)
+    }
+}
+
+fn species_from(matches: &ArgMatches) -> Result<Vec<Species>,CatchAllError> {
+    match matches.values_of("species") {
+        Some(specs) => specs.map(parse_species).collect(),
+        None => {
+            let len_arg = |name: &str| parse_cycle_len(matches.value_of(name).unwrap_or_default());
+            Ok(vec![Species {
+                name: "lanternfish".to_string(),
+                cycle_len: len_arg("fish_cycle")?,
+                new_cycle_len: len_arg("fish_new_cycle")?,
+                mortality: 0
+            }])
+        }
+    }
+}
+
+fn parse_cycle_len(len: &str) -> Result<usize,CatchAllError> {
+    match len.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(CatchAllError::new(format!("cycle length {} should be a positive int", len)))
+    }
+}
+
+fn parse_species(spec: &str) -> Result<Species,CatchAllError> {
+    let fields: Vec<&str> = spec.split(':').collect();
+    let (name, cycle, new_cycle, mortality) = match fields[..] {
+        [name, cycle, new_cycle] => (name, cycle, new_cycle, "0"),
+        [name, cycle, new_cycle, mortality] => (name, cycle, new_cycle, mortality),
+        _ => return Err(CatchAllError::new(format!("species {} should be NAME:CYCLE:NEW[:MORTALITY]", spec)))
+    };
+    let mortality = match mortality.trim_end_matches('%').parse::<u64>() {
+        Ok(m) if m <= 100 => m,
+        _ => return Err(CatchAllError::new(format!("mortality {} should be a percentage from 0 to 100", mortality)))
+    };
+    Ok(Species {
+        name: name.to_string(),
+        cycle_len: parse_cycle_len(cycle)?,
+        new_cycle_len: parse_cycle_len(new_cycle)?,
+        mortality
+    })
+}
+
+// the starting timers for each species, one input line each
+fn parse_fish(input: &str, species: &[Species]) -> Result<Vec<Vec<usize>>,CatchAllError> {
+    let lines: Vec<&str> = input.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() != species.len() {
+        return Err(CatchAllError::new(format!(
+                    "input has {} lines of timers but there are {} species", lines.len(), species.len())));
+    }
+
+    lines.iter().zip(species).map(|(line, s)| {
+        let (_, timers) = parse::parse(line)
+            .map_err(|err| CatchAllError::new(format!("couldn't parse timers for {}: {}", s.name, err)))?;
+        match timers.iter().find(|&&t| t >= s.timers()) {
+            Some(t) => Err(CatchAllError::new(format!("timer {} is too long for {}", t, s.name))),
+            None => Ok(timers)
+        }
+    }).collect()
+}
+
+fn parse_modulus(m: &str) -> Result<u64,CatchAllError> {
+    match m.parse::<u64>() {
+        Ok(p) if p > 1 => Ok(p),
+        _ => Err(CatchAllError::new(format!("modulus {} should be an int greater than 1", m)))
+    }
+}
 
-    debug!("init_fish: {:?}", init_fish);
+// prints the total, and the count for each species when there's more than one
+fn print_counts<N: fmt::Display>(label: &str, species: &[Species], counts: &[N], total: &N, suffix: &str) {
+    println!("Day 6; {}: {}{}", label, total, suffix);
+    if species.len() > 1 {
+        for (s, count) in species.iter().zip(counts) {
+            println!("Day 6;   {}: {}{}", s.name, count, suffix);
+        }
+    }
+}
 
-    let result_a = day_6a(&init_fish).expect("error during part a");
-    println!("Day 6; Part A: {}", result_a);
+fn report_step<N: FishCounter>(species: &[Species], init_fish: &[Vec<usize>], queries: &[(String, u64)]) {
+    for (label, days) in queries {
+        let counts = count_by_species::<N>(species, init_fish, *days)
+            .unwrap_or_else(|err| panic!("error during {}: {}", label, err));
+        let total = counts.iter()
+            .try_fold(N::from_count(0), |acc, n| acc.checked_add(n))
+            .ok_or_else(|| overflow_err::<N>("the total"))
+            .unwrap_or_else(|err| panic!("error during {}: {}", label, err));
+        print_counts(label, species, &counts, &total, "");
+    }
+}
 
-    let result_b = day_6b(&init_fish).expect("error during part b");
-    println!("Day 6; Part B: {}", result_b);
+fn report_matrix<N: matrix::Count>(species: &[Species], init_fish: &[Vec<usize>], queries: &[(String, u64)],
+                                   lift: impl Fn(u64) -> N, suffix: &str)
+{
+    if let Some(s) = species.iter().find(|s| s.mortality > 0) {
+        panic!("{} has mortality, which the matrix method can't model; use --fish-method step", s.name);
+    }
+    for (label, days) in queries {
+        let counts: Vec<N> = species.iter().zip(init_fish)
+            .map(|(s, fish)| matrix::count_fish(fish, s.cycle_len, s.new_cycle_len, *days, &lift))
+            .collect();
+        let total = counts.iter().fold(lift(0), |acc, n| acc.add(n));
+        print_counts(label, species, &counts, &total, suffix);
+    }
 }
 
 mod parse {
@@ -39,49 +262,379 @@ mod parse {
     }
 }
 
+// the number of fish with one timer value. fixed width counters report
+// overflow rather than wrapping.
+trait FishCounter: Clone + fmt::Display {
+    const NAME: &'static str;
+    fn from_count(n: u64) -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn to_f64(&self) -> f64;
+    // self * num / den, rounded down
+    fn checked_scale(&self, num: u64, den: u64) -> Option<Self>;
+}
+
+impl FishCounter for i64 {
+    const NAME: &'static str = "i64";
+
+    fn from_count(n: u64) -> Self {
+        n as i64
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+
+    // widened so only a result that doesn't fit counts as overflow
+    fn checked_scale(&self, num: u64, den: u64) -> Option<Self> {
+        i64::try_from(*self as i128 * num as i128 / den as i128).ok()
+    }
+}
+
+impl FishCounter for BigUint {
+    const NAME: &'static str = "big";
+
+    fn from_count(n: u64) -> Self {
+        BigUint::from_u64(n)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self.add(other))
+    }
+
+    fn to_f64(&self) -> f64 {
+        BigUint::to_f64(self)
+    }
+
+    fn checked_scale(&self, num: u64, den: u64) -> Option<Self> {
+        Some(self.mul(&BigUint::from_u64(num)).div_small(den as u32))
+    }
+}
+
+fn overflow_err<N: FishCounter>(what: &str) -> CatchAllError {
+    CatchAllError::new(format!("{} overflowed the {} fish counter; try --fish-counter big", what, N::NAME))
+}
+
 #[derive(Debug)]
-struct Population {
-    counters: VecDeque<i64>,
+struct Population<N> {
+    counters: VecDeque<N>,
     new_idx: usize,
-    reset_idx: usize
+    reset_idx: usize,
+    survival: u64 // percent of spawning fish that live on
 }
 
-impl Population {
-    fn new(init_fish: &[usize], cycle_len: usize, new_cycle_len: usize) -> Population {
-        let mut counters: VecDeque<i64> = VecDeque::with_capacity(new_cycle_len);
-        (0..new_cycle_len).for_each(|_| counters.push_back(0));
-
+impl<N: FishCounter> Population<N> {
+    fn new(init_fish: &[usize], species: &Species) -> Population<N> {
+        let mut timers = vec![0; species.timers()];
         for &fish in init_fish {
-            counters[fish] = counters[fish] + 1;
+            timers[fish] += 1;
+        }
+        let counters = timers.into_iter().map(N::from_count).collect();
+        Population {
+            counters,
+            new_idx: species.new_cycle_len - 1,
+            reset_idx: species.cycle_len - 1,
+            survival: 100 - species.mortality
         }
-        Population { counters, new_idx: new_cycle_len - 1, reset_idx: cycle_len - 1 }
     }
 
-    fn sim_step(&mut self) {
+    fn sim_step(&mut self) -> Option<()> {
         let num_breeding = self.counters.pop_front().expect("counters should never be empty");
-        self.counters.push_back(num_breeding);
-        self.counters[self.reset_idx] += num_breeding;
+        self.counters.push_back(N::from_count(0));
+        let survivors = if self.survival == 100 {
+            num_breeding.clone()
+        } else {
+            num_breeding.checked_scale(self.survival, 100)?
+        };
+        self.counters[self.reset_idx] = self.counters[self.reset_idx].checked_add(&survivors)?;
+        self.counters[self.new_idx] = self.counters[self.new_idx].checked_add(&num_breeding)?;
+        Some(())
     }
 
-    fn simulate_for(&mut self, days: i32) {
-        for _ in 0..days {
+    fn simulate_for(&mut self, days: u64) -> Result<(),CatchAllError> {
+        for day in 1..=days {
             self.sim_step()
+                .ok_or_else(|| overflow_err::<N>(&format!("day {}", day)))?;
         }
+        Ok(())
     }
 
-    fn count_fish(&self) -> i64 {
-        self.counters.iter().sum()
+    fn count_fish(&self) -> Result<N,CatchAllError> {
+        let zero = N::from_count(0);
+        self.counters.iter()
+            .try_fold(zero, |acc, n| acc.checked_add(n))
+            .ok_or_else(|| overflow_err::<N>("the total"))
     }
 }
 
-fn day_6a(init_fish: &[usize]) -> Result<i64,CatchAllError> {
-    let mut population = Population::new(init_fish, 7, 9);
-    population.simulate_for(80);
-    Ok(population.count_fish())
+// simulates every species for the given days, optionally writing each day's
+// counts to a CSV file, and gives each species' total on every day
+fn history_totals<N: FishCounter>(species: &[Species], init_fish: &[Vec<usize>], days: u64, csv_file: Option<&str>) ->
+    Result<Vec<Vec<f64>>,CatchAllError>
+{
+    let mut populations: Vec<Population<N>> = species.iter().zip(init_fish)
+        .map(|(s, fish)| Population::new(fish, s))
+        .collect();
+    let max_timers = species.iter().map(|s| s.timers()).max().unwrap_or(0);
+    let timer_cols: Vec<String> = (0..max_timers).map(|t| format!("t{}", t)).collect();
+    let mut csv = format!("day,species,total,growth,{}\n", timer_cols.join(","));
+    let mut totals = vec![Vec::new(); species.len()];
+
+    for day in 0..=days {
+        if day > 0 {
+            for (s, population) in species.iter().zip(populations.iter_mut()) {
+                population.simulate_for(1)
+                    .map_err(|_| overflow_err::<N>(&format!("day {} of {}", day, s.name)))?;
+            }
+        }
+        for ((s, population), totals) in species.iter().zip(&populations).zip(totals.iter_mut()) {
+            let total = population.count_fish()?;
+            let growth = match totals.last() {
+                Some(&prev) if prev > 0.0 => format!("{:.6}", total.to_f64() / prev),
+                _ => String::new()
+            };
+            let mut cols: Vec<String> = population.counters.iter().map(|n| n.to_string()).collect();
+            cols.resize(max_timers, String::new());
+            csv.push_str(&format!("{},{},{},{},{}\n", day, s.name, total, growth, cols.join(",")));
+            totals.push(total.to_f64());
+        }
+    }
+
+    if let Some(filename) = csv_file {
+        fs::write(filename, csv)
+            .map_err(|err| CatchAllError::new(format!("could not write {}: {}", filename, err)))?;
+        println!("Day 6; wrote {} days of history to {}", days, filename);
+    }
+    Ok(totals)
+}
+
+// how the day to day growth ratio compares with the dominant eigenvalue of the
+// species' transition matrix, which it converges to over long runs
+fn report_growth(species: &Species, totals: &[f64]) {
+    let eigenvalue = growth::dominant_eigenvalue(species);
+    println!("Day 6; {} grows by {:.6} a day in the long run", species.name, eigenvalue);
+
+    let ratios: Vec<(usize, f64)> = totals.windows(2).enumerate()
+        .filter(|(_, w)| w[0] > 0.0)
+        .map(|(day, w)| (day + 1, w[1] / w[0]))
+        .collect();
+    // checkpoints at doubling day counts show the convergence
+    let mut checkpoints: Vec<usize> = (0..).map(|k| 1 << k).take_while(|&d| d < totals.len()).collect();
+    checkpoints.push(totals.len() - 1);
+    checkpoints.dedup();
+    for (day, ratio) in ratios.iter().filter(|(day, _)| checkpoints.contains(day)) {
+        println!("Day 6;   day {}: grew by {:.6}, off by {:.2e}", day, ratio, (ratio - eigenvalue).abs());
+    }
+}
+
+// every species is simulated side by side over the same days
+fn count_by_species<N: FishCounter>(species: &[Species], init_fish: &[Vec<usize>], days: u64) ->
+    Result<Vec<N>,CatchAllError>
+{
+    species.iter().zip(init_fish).map(|(s, fish)| {
+        let mut population = Population::new(fish, s);
+        population.simulate_for(days)
+            .map_err(|err| CatchAllError::new(format!("{}: {}", s.name, err)))?;
+        population.count_fish()
+    }).collect()
+}
+
+// counts fish by raising the one day transition matrix to the number of days,
+// squaring as it goes, so any number of days takes O(log days) matrix products
+mod matrix {
+    use std::fmt;
+
+    use crate::util::bignum::BigUint;
+
+    // the arithmetic fish counts need
+    pub trait Count: Clone + fmt::Display {
+        fn add(&self, other: &Self) -> Self;
+        fn mul(&self, other: &Self) -> Self;
+    }
+
+    impl Count for BigUint {
+        fn add(&self, other: &Self) -> Self { BigUint::add(self, other) }
+        fn mul(&self, other: &Self) -> Self { BigUint::mul(self, other) }
+    }
+
+    // a count reduced modulo some p, for day counts where the exact answer
+    // would have billions of digits
+    #[derive(Copy,Clone,Debug)]
+    pub struct Modular {
+        value: u64,
+        modulus: u64
+    }
+
+    impl Modular {
+        pub fn new(value: u64, modulus: u64) -> Modular {
+            Modular { value: value % modulus, modulus }
+        }
+    }
+
+    impl fmt::Display for Modular {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.value)
+        }
+    }
+
+    impl Count for Modular {
+        fn add(&self, other: &Self) -> Self {
+            let value = (self.value as u128 + other.value as u128) % self.modulus as u128;
+            Modular { value: value as u64, modulus: self.modulus }
+        }
+
+        fn mul(&self, other: &Self) -> Self {
+            let value = (self.value as u128 * other.value as u128) % self.modulus as u128;
+            Modular { value: value as u64, modulus: self.modulus }
+        }
+    }
+
+    // a square matrix, row by row
+    struct Matrix<N> {
+        size: usize,
+        cells: Vec<N>
+    }
+
+    impl<N: Count> Matrix<N> {
+        fn from_fn(size: usize, f: impl Fn(usize, usize) -> N) -> Matrix<N> {
+            let cells = (0..size * size).map(|idx| f(idx / size, idx % size)).collect();
+            Matrix { size, cells }
+        }
+
+        fn get(&self, i: usize, j: usize) -> &N {
+            &self.cells[i * self.size + j]
+        }
+
+        fn mul(&self, other: &Matrix<N>) -> Matrix<N> {
+            Matrix::from_fn(self.size, |i, j| {
+                (1..self.size).fold(self.get(i, 0).mul(other.get(0, j)),
+                                    |acc, k| acc.add(&self.get(i, k).mul(other.get(k, j))))
+            })
+        }
+
+        fn pow(&self, mut exp: u64, identity: Matrix<N>) -> Matrix<N> {
+            let mut result = identity;
+            let mut square = Matrix { size: self.size, cells: self.cells.clone() };
+            while exp > 0 {
+                if exp % 2 == 1 {
+                    result = result.mul(&square);
+                }
+                exp /= 2;
+                if exp > 0 {
+                    square = square.mul(&square);
+                }
+            }
+            result
+        }
+    }
+
+    // maps the fish per timer value on one day to the next: every timer counts
+    // down, and the fish at 0 go back to cycle_len - 1 and each spawn a new fish
+    // at new_cycle_len - 1
+    fn transition<N: Count>(cycle_len: usize, new_cycle_len: usize, lift: &impl Fn(u64) -> N) -> Matrix<N> {
+        Matrix::from_fn(cycle_len.max(new_cycle_len), |i, j| {
+            let from_next = (j == i + 1) as u64;
+            let reset = (j == 0 && i == cycle_len - 1) as u64;
+            let spawn = (j == 0 && i == new_cycle_len - 1) as u64;
+            lift(from_next + reset + spawn)
+        })
+    }
+
+    pub fn count_fish<N: Count>(init_fish: &[usize], cycle_len: usize, new_cycle_len: usize, days: u64,
+                                lift: &impl Fn(u64) -> N) -> N
+    {
+        let size = cycle_len.max(new_cycle_len);
+        let mut timers = vec![0; size];
+        for &fish in init_fish {
+            timers[fish] += 1;
+        }
+
+        let identity = Matrix::from_fn(size, |i, j| lift((i == j) as u64));
+        let after = transition(cycle_len, new_cycle_len, lift).pow(days, identity);
+
+        let mut total = lift(0);
+        for i in 0..size {
+            for (j, &count) in timers.iter().enumerate() {
+                total = total.add(&after.get(i, j).mul(&lift(count)));
+            }
+        }
+        total
+    }
 }
 
-fn day_6b(init_fish: &[usize]) -> Result<i64,CatchAllError> {
-    let mut population = Population::new(init_fish, 7, 9);
-    population.simulate_for(256);
-    Ok(population.count_fish())
+mod growth {
+    use super::Species;
+
+    const MAX_ITERATIONS: usize = 100_000;
+    const TOLERANCE: f64 = 1e-12;
+
+    // power iteration on the transition matrix, with mortality as a fraction
+    // rather than rounded. the matrix has the identity added so that species
+    // whose timers cycle with a fixed period still converge; that shifts every
+    // eigenvalue up by one, which is taken off again at the end.
+    pub fn dominant_eigenvalue(species: &Species) -> f64 {
+        let size = species.timers();
+        let survival = (100 - species.mortality) as f64 / 100.0;
+        let step = |v: &[f64]| -> Vec<f64> {
+            let mut next: Vec<f64> = (0..size).map(|i| v[i] + v.get(i + 1).copied().unwrap_or(0.0)).collect();
+            next[species.cycle_len - 1] += survival * v[0];
+            next[species.new_cycle_len - 1] += v[0];
+            next
+        };
+
+        let mut v = vec![1.0 / size as f64; size];
+        let mut eigenvalue = 0.0;
+        for _ in 0..MAX_ITERATIONS {
+            let next = step(&v);
+            let sum: f64 = next.iter().sum();
+            let converged = (sum - eigenvalue).abs() < TOLERANCE;
+            eigenvalue = sum;
+            v = next.iter().map(|n| n / sum).collect();
+            if converged {
+                break;
+            }
+        }
+        eigenvalue - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [usize; 5] = [3, 4, 3, 1, 2];
+
+    fn lanternfish() -> Species {
+        Species { name: "lanternfish".to_string(), cycle_len: 7, new_cycle_len: 9, mortality: 0 }
+    }
+
+    // checks the matrix method against both step counters and gives the count
+    fn count_all_ways(days: u64) -> i64 {
+        let species = [lanternfish()];
+        let init_fish = [EXAMPLE.to_vec()];
+
+        let small = count_by_species::<i64>(&species, &init_fish, days).unwrap()[0];
+        let big = count_by_species::<BigUint>(&species, &init_fish, days).unwrap()[0].clone();
+        let matrix = matrix::count_fish(&EXAMPLE, 7, 9, days, &BigUint::from_u64);
+        assert_eq!(big.to_string(), small.to_string(), "big step counter after {} days", days);
+        assert_eq!(matrix, big, "matrix after {} days", days);
+        small
+    }
+
+    #[test]
+    fn example() {
+        assert_eq!(count_all_ways(18), 26);
+        assert_eq!(count_all_ways(80), 5934);
+        assert_eq!(count_all_ways(256), 26984457539);
+    }
+
+    #[test]
+    fn every_day() {
+        for days in 0..=256 {
+            count_all_ways(days);
+        }
+    }
 }
@@ -1,11 +1,56 @@
+use std::fs;
 use std::io::BufReader;
 use std::io::Read;
 use std::fs::File;
 use std::cmp;
+use std::convert::TryFrom;
+
+use clap::{Arg, ArgMatches};
 
 use crate::util::error::CatchAllError;
 
-pub fn run(mut input_reader: BufReader<File>) {
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("align_method")
+            .long("align-method")
+            .takes_value(true)
+            .possible_values(&["brute", "closed", "search", "check"])
+            .default_value("brute")
+            .help("Day 7: tries every position, jumps to the median or mean, searches the convex cost \
+                   curve, or does all that apply, checking that they agree"),
+        Arg::with_name("cost_a")
+            .long("cost-a")
+            .takes_value(true)
+            .value_name("MODEL")
+            .default_value("linear")
+            .help("Day 7: cost model for part A: linear, triangular, quadratic, capped:N, or expr:FORMULA \
+                   in d, the distance moved, e.g. expr:d^2+3*d"),
+        Arg::with_name("cost_b")
+            .long("cost-b")
+            .takes_value(true)
+            .value_name("MODEL")
+            .default_value("triangular")
+            .help("Day 7: cost model for part B, as for --cost-a"),
+        Arg::with_name("crab_weights")
+            .long("crab-weights")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Day 7: comma separated fuel use per crab, in input order, multiplying each crab's cost"),
+        Arg::with_name("cost_curve")
+            .long("cost-curve")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Day 7: writes both parts' total cost at every end position to a CSV file"),
+        Arg::with_name("cost_chart")
+            .long("cost-chart")
+            .help("Day 7: draws both parts' cost curves as ASCII charts"),
+        Arg::with_name("align_report")
+            .long("align-report")
+            .help("Day 7: lists every optimal end position and each crab's fuel use at the first"),
+    ]
+}
+
+pub fn run(mut input_reader: BufReader<File>, matches: &ArgMatches) {
     let mut input_str = String::new();
     input_reader.read_to_string(&mut input_str)
         .expect("could not read input");
@@ -13,11 +58,47 @@ pub fn run(mut input_reader: BufReader<File>) {
 	let (_, init_poss): (_, Vec<usize>) = parse::parse(&input_str)
         .expect("couldn't parse input");
 
-    let result_a = day_7a(&init_poss).expect("error during part a");
+    let weights = matches.value_of("crab_weights").map(|filename| {
+        let weights_str = fs::read_to_string(filename).expect("could not read crab weights");
+        let (_, weights) = parse::parse(weights_str.trim()).expect("couldn't parse crab weights");
+        weights
+    });
+    let crabs = Crabs::new(init_poss, weights).expect("invalid crab weights");
+
+    let method = Method::from_arg(matches.value_of("align_method"));
+    let model_a = CostModel::parse(matches.value_of("cost_a").unwrap_or_default())
+        .expect("invalid cost model for part a");
+    let model_b = CostModel::parse(matches.value_of("cost_b").unwrap_or_default())
+        .expect("invalid cost model for part b");
+
+    let result_a = day_7a(&crabs, &model_a, method).expect("error during part a");
     println!("Day 7; Part A: {}", result_a);
 
-    let result_b = day_7b(&init_poss).expect("error during part b");
+    let result_b = day_7b(&crabs, &model_b, method).expect("error during part b");
     println!("Day 7; Part B: {}", result_b);
+
+    if matches.is_present("cost_curve") || matches.is_present("cost_chart") || matches.is_present("align_report") {
+        let curve_a = cost_curve(&crabs, &model_a).expect("error during part a");
+        let curve_b = cost_curve(&crabs, &model_b).expect("error during part b");
+
+        if let Some(filename) = matches.value_of("cost_curve") {
+            let mut csv = "position,part_a,part_b\n".to_string();
+            for (pos, (a, b)) in curve_a.iter().zip(&curve_b).enumerate() {
+                csv.push_str(&format!("{},{},{}\n", pos, a, b));
+            }
+            fs::write(filename, csv).expect("could not write cost curve");
+            println!("Day 7; wrote cost curves to {}", filename);
+        }
+
+        for (part, curve, model) in &[("Part A", &curve_a, &model_a), ("Part B", &curve_b, &model_b)] {
+            if matches.is_present("cost_chart") {
+                println!("Day 7; {} cost curve:\n{}", part, chart::ascii(curve));
+            }
+            if matches.is_present("align_report") {
+                report_alignment(part, &crabs, model, curve).expect("error reporting alignment");
+            }
+        }
+    }
 }
 
 mod parse {
@@ -39,35 +120,650 @@ fn distance(p1: usize, p2: usize) -> i64 {
     (p1 as i64 - p2 as i64).abs()
 }
 
-fn day_7a(init_poss: &[usize]) -> Result<i64,CatchAllError> {
-    both_parts(init_poss, distance)
+// crab positions, and how much fuel each burns per unit of cost
+struct Crabs {
+    poss: Vec<usize>,
+    weights: Vec<i64>
+}
+
+impl Crabs {
+    fn new(poss: Vec<usize>, weights: Option<Vec<usize>>) -> Result<Crabs,CatchAllError> {
+        let weights = match weights {
+            Some(weights) if weights.len() != poss.len() =>
+                return Err(CatchAllError::new(format!(
+                            "{} weights given for {} crabs", weights.len(), poss.len()))),
+            Some(weights) => weights.into_iter().map(|w| w as i64).collect(),
+            None => vec![1; poss.len()]
+        };
+        Ok(Crabs { poss, weights })
+    }
+
+    fn total_weight(&self) -> i64 {
+        self.weights.iter().sum()
+    }
+}
+
+// how much fuel moving a crab some distance takes
+#[derive(Clone,Debug)]
+enum CostModel {
+    Linear,
+    Triangular,
+    Quadratic,
+    Capped(i64), // linear up to the cap, and flat after it
+    Formula(expr::Expr)
+}
+
+impl CostModel {
+    fn parse(model: &str) -> Result<CostModel,CatchAllError> {
+        match model.split_once(':') {
+            None if model == "linear" => Ok(CostModel::Linear),
+            None if model == "triangular" => Ok(CostModel::Triangular),
+            None if model == "quadratic" => Ok(CostModel::Quadratic),
+            Some(("capped", cap)) => match cap.parse::<i64>() {
+                Ok(cap) if cap >= 0 => Ok(CostModel::Capped(cap)),
+                _ => Err(CatchAllError::new(format!("cap {} should be a non-negative int", cap)))
+            },
+            Some(("expr", formula)) => Ok(CostModel::Formula(expr::parse(formula)?)),
+            _ => Err(CatchAllError::new(format!(
+                        "unknown cost model {}; expected linear, triangular, quadratic, capped:N or expr:FORMULA",
+                        model)))
+        }
+    }
+
+    fn cost(&self, distance: i64) -> Result<i64,CatchAllError> {
+        let overflow = || CatchAllError::new(format!("overflow computing the cost of moving {}", distance));
+        match self {
+            CostModel::Linear => Ok(distance),
+            CostModel::Triangular => i64::try_from(distance as i128 * (distance as i128 + 1) / 2)
+                .map_err(|_| overflow()),
+            CostModel::Quadratic => distance.checked_mul(distance).ok_or_else(overflow),
+            CostModel::Capped(cap) => Ok(cmp::min(distance, *cap)),
+            CostModel::Formula(formula) => formula.eval(distance)
+        }
+    }
+
+    // whether the total cost is convex in the end position, which holds when
+    // the cost never falls as the distance grows and rises at least as fast
+    // each step. the named models are known to be; formulas have to be
+    // checked at every distance up to max_distance.
+    fn is_convex(&self, max_distance: usize) -> Result<bool,CatchAllError> {
+        match self {
+            CostModel::Linear | CostModel::Triangular | CostModel::Quadratic => Ok(true),
+            CostModel::Capped(cap) => Ok(*cap >= max_distance as i64),
+            CostModel::Formula(_) => {
+                let mut prev_step: Option<i128> = None;
+                let mut prev_cost = self.cost(0)? as i128;
+                for d in 1..=max_distance as i64 {
+                    let cost = self.cost(d)? as i128;
+                    let step = cost - prev_cost;
+                    if step < 0 || prev_step.is_some_and(|prev| step < prev) {
+                        return Ok(false);
+                    }
+                    prev_step = Some(step);
+                    prev_cost = cost;
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    // where the closed form solution could be, for the models that have one
+    fn candidates(&self) -> Option<fn(&Crabs) -> Vec<usize>> {
+        match self {
+            CostModel::Linear => Some(median_candidates),
+            CostModel::Triangular | CostModel::Quadratic => Some(mean_candidates),
+            _ => None
+        }
+    }
 }
 
+#[derive(Copy,Clone,Debug)]
+enum Method {
+    Brute,
+    Closed,
+    Search,
+    Check
+}
 
-fn day_7b(init_poss: &[usize]) -> Result<i64,CatchAllError> {
-    fn cost(p1: usize, p2: usize) -> i64 {
-        let distance = distance(p1,p2);
-        (distance * (distance + 1)) / 2
+impl Method {
+    fn from_arg(arg: Option<&str>) -> Method {
+        match arg {
+            Some("closed") => Method::Closed,
+            Some("search") => Method::Search,
+            Some("check") => Method::Check,
+            _ => Method::Brute
+        }
     }
+}
+
+fn day_7a(crabs: &Crabs, model: &CostModel, method: Method) -> Result<i64,CatchAllError> {
+    both_parts(crabs, model, method)
+}
+
 
-    both_parts(init_poss, cost)
+fn day_7b(crabs: &Crabs, model: &CostModel, method: Method) -> Result<i64,CatchAllError> {
+    both_parts(crabs, model, method)
 }
 
-fn both_parts<F>(init_poss: &[usize], cost_calc: F) -> 
-    Result<i64,CatchAllError> 
+fn both_parts(crabs: &Crabs, model: &CostModel, method: Method) -> Result<i64,CatchAllError> {
+    let board_size = board_size(crabs)?;
+    let cost_calc = |p1: usize, p2: usize| model.cost(distance(p1, p2));
+
+    let closed = || match model.candidates() {
+        Some(candidates) => closed_form(crabs, &cost_calc, candidates),
+        None => Err(CatchAllError::new(
+                "the cost model has no closed form; try --align-method search or brute".to_string()))
+    };
+    let search = || if model.is_convex(board_size)? {
+        convex_search(crabs, &cost_calc)
+    } else {
+        Err(CatchAllError::new(format!(
+                    "the cost model isn't convex over distances 0 to {}, so search could miss the minimum; \
+                     try --align-method brute", board_size)))
+    };
+
+    match method {
+        Method::Brute => brute_force(crabs, &cost_calc),
+        Method::Closed => closed(),
+        Method::Search => search(),
+        Method::Check => {
+            let brute = brute_force(crabs, &cost_calc)?;
+            // only the solvers that apply to this model are checked
+            let mut others = Vec::new();
+            if model.candidates().is_some() {
+                others.push(("closed form", closed()?));
+            }
+            if model.is_convex(board_size)? {
+                others.push(("search", search()?));
+            }
+            for (name, found) in others {
+                if found != brute {
+                    return Err(CatchAllError::new(format!(
+                                "brute force found {} but {} found {}", brute, name, found)));
+                }
+            }
+            Ok(brute)
+        }
+    }
+}
+
+// the total cost of ending at each position from 0 to the furthest crab
+fn cost_curve(crabs: &Crabs, model: &CostModel) -> Result<Vec<i64>,CatchAllError> {
+    let board_size = board_size(crabs)?;
+    let cost_calc = |p1: usize, p2: usize| model.cost(distance(p1, p2));
+    (0..=board_size).map(|end_pos| total_cost(crabs, &cost_calc, end_pos).and_then(narrow)).collect()
+}
+
+// every position with the lowest total cost, in order
+fn optimal_positions(curve: &[i64]) -> Vec<usize> {
+    let min_cost = curve.iter().copied().min().unwrap_or(0);
+    curve.iter().enumerate()
+        .filter(|(_, &cost)| cost == min_cost)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+fn report_alignment(part: &str, crabs: &Crabs, model: &CostModel, curve: &[i64]) -> Result<(),CatchAllError> {
+    let optimal = optimal_positions(curve);
+    let best = *optimal.first()
+        .ok_or(CatchAllError::new("no positions input".to_string()))?;
+    let positions: Vec<String> = optimal.iter().map(|p| p.to_string()).collect();
+    println!("Day 7; {}: {} fuel, at {}{}", part, curve[best], positions.join(", "),
+             if optimal.len() > 1 { " (tied)" } else { "" });
+
+    for (idx, (&pos, &weight)) in crabs.poss.iter().zip(&crabs.weights).enumerate() {
+        let moved = distance(pos, best);
+        println!("Day 7;   crab {} at {} moves {} to {}: {} fuel",
+                 idx + 1, pos, moved, best, weight * model.cost(moved)?);
+    }
+    Ok(())
+}
+
+// totals are kept wide so the solvers can compare positions whose total
+// wouldn't fit an i64, as long as the minimum they settle on does
+fn total_cost<F>(crabs: &Crabs, cost_calc: &F, end_pos: usize) -> Result<i128,CatchAllError>
 where
-    F: Fn(usize, usize) -> i64
+    F: Fn(usize, usize) -> Result<i64,CatchAllError>
 {
-    let board_size = init_poss.iter().max()
-        .ok_or(CatchAllError::new("no positions input".to_string()))?;
-    let mut min_cost: i64 = i64::MAX;
-    for end_pos in 0..=*board_size {
-        let mut cur_cost = 0;
-        for init_pos in init_poss {
-            cur_cost += cost_calc(*init_pos,end_pos);
+    let mut total: i128 = 0;
+    for (&init_pos, &weight) in crabs.poss.iter().zip(&crabs.weights) {
+        total += weight as i128 * cost_calc(init_pos, end_pos)? as i128;
+    }
+    Ok(total)
+}
+
+fn narrow(total: i128) -> Result<i64,CatchAllError> {
+    i64::try_from(total)
+        .map_err(|_| CatchAllError::new(format!("total cost {} doesn't fit an i64", total)))
+}
+
+fn board_size(crabs: &Crabs) -> Result<usize,CatchAllError> {
+    crabs.poss.iter().max().copied()
+        .ok_or(CatchAllError::new("no positions input".to_string()))
+}
+
+fn brute_force<F>(crabs: &Crabs, cost_calc: &F) ->
+    Result<i64,CatchAllError>
+where
+    F: Fn(usize, usize) -> Result<i64,CatchAllError>
+{
+    let board_size = board_size(crabs)?;
+    let mut min_cost: i128 = i128::MAX;
+    for end_pos in 0..=board_size {
+        min_cost = cmp::min(min_cost, total_cost(crabs, cost_calc, end_pos)?);
+    }
+
+    narrow(min_cost)
+}
+
+fn closed_form<F>(crabs: &Crabs, cost_calc: &F, candidates: fn(&Crabs) -> Vec<usize>) ->
+    Result<i64,CatchAllError>
+where
+    F: Fn(usize, usize) -> Result<i64,CatchAllError>
+{
+    let board_size = board_size(crabs)?;
+    candidates(crabs).into_iter()
+        .filter(|&end_pos| end_pos <= board_size)
+        .map(|end_pos| total_cost(crabs, cost_calc, end_pos))
+        .collect::<Result<Vec<_>,_>>()?
+        .into_iter()
+        .min()
+        .ok_or(CatchAllError::new("no candidate positions".to_string()))
+        .and_then(narrow)
+}
+
+// the sum of distances is smallest at the median, weighted by fuel use
+fn median_candidates(crabs: &Crabs) -> Vec<usize> {
+    let mut sorted: Vec<(usize, i64)> = crabs.poss.iter().copied().zip(crabs.weights.iter().copied()).collect();
+    sorted.sort_unstable();
+    let total = crabs.total_weight();
+    let mut seen = 0;
+    for (pos, weight) in sorted {
+        seen += weight;
+        if 2 * seen >= total {
+            return vec![pos];
         }
-        min_cost = cmp::min(min_cost, cur_cost);
     }
+    vec![0]
+}
 
-    Ok(min_cost)
+// the triangular cost is n^2/2 + n/2 and the quadratic one n^2, so the sum is
+// smallest within half a step of the weighted mean. checking one either side
+// of it covers the rounding.
+fn mean_candidates(crabs: &Crabs) -> Vec<usize> {
+    let total = crabs.total_weight();
+    if total == 0 {
+        return vec![0];
+    }
+    let weighted_sum: i64 = crabs.poss.iter().zip(&crabs.weights).map(|(&p, &w)| p as i64 * w).sum();
+    let mean = (weighted_sum / total) as usize;
+    (mean.saturating_sub(1)..=mean + 1).collect()
+}
+
+// a sum of convex costs is convex, so the minimum is where the cost stops
+// falling. this binary searches for that point on the cost differences,
+// the integer version of a ternary search.
+fn convex_search<F>(crabs: &Crabs, cost_calc: &F) ->
+    Result<i64,CatchAllError>
+where
+    F: Fn(usize, usize) -> Result<i64,CatchAllError>
+{
+    let (mut lo, mut hi) = (0, board_size(crabs)?);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if total_cost(crabs, cost_calc, mid)? <= total_cost(crabs, cost_calc, mid + 1)? {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    narrow(total_cost(crabs, cost_calc, lo)?)
+}
+
+mod chart {
+    use super::optimal_positions;
+
+    const WIDTH: usize = 72;
+    const HEIGHT: usize = 16;
+
+    // plots the curve with the cost rising upwards. when there are more
+    // positions than columns each column shows the cheapest of its positions,
+    // so the minimum is never lost. optimal positions are marked with ^.
+    pub fn ascii(curve: &[i64]) -> String {
+        let per_col = curve.len().div_ceil(WIDTH).max(1);
+        let cols: Vec<i64> = curve.chunks(per_col)
+            .map(|chunk| chunk.iter().copied().min().unwrap_or(0))
+            .collect();
+        let min = cols.iter().copied().min().unwrap_or(0);
+        let max = cols.iter().copied().max().unwrap_or(0);
+        let level = |cost: i64| -> usize {
+            if max == min {
+                0
+            } else {
+                ((cost - min) as i128 * (HEIGHT - 1) as i128 / (max - min) as i128) as usize
+            }
+        };
+
+        let label_width = max.to_string().len().max(min.to_string().len());
+        let mut out = String::new();
+        for row in (0..HEIGHT).rev() {
+            let label = match row {
+                r if r == HEIGHT - 1 => max.to_string(),
+                0 => min.to_string(),
+                _ => String::new()
+            };
+            let points: String = cols.iter().map(|&c| if level(c) == row { '*' } else { ' ' }).collect();
+            out.push_str(&format!("{:>w$} |{}\n", label, points.trim_end(), w = label_width));
+        }
+        out.push_str(&format!("{:>w$} +{}\n", "", "-".repeat(cols.len()), w = label_width));
+
+        let optimal = optimal_positions(curve);
+        let markers: String = (0..cols.len())
+            .map(|col| if optimal.iter().any(|p| p / per_col == col) { '^' } else { ' ' })
+            .collect();
+        out.push_str(&format!("{:>w$}  {}\n", "", markers.trim_end(), w = label_width));
+
+        let last = (curve.len().max(1) - 1).to_string();
+        let gap = cols.len().saturating_sub(1 + last.len()).max(1);
+        out.push_str(&format!("{:>w$}  0{}{}", "", " ".repeat(gap), last, w = label_width));
+        out
+    }
+}
+
+// custom costs as a formula in d, the distance moved, e.g. "d^2 + 3*d" or
+// "min(d, 10) * 2". supports + - * / % ^, parentheses and min, max and abs.
+mod expr {
+    use std::convert::TryFrom;
+
+    use nom::{
+        IResult,
+        branch::alt,
+        character::complete::{
+            alpha1,
+            char,
+            digit1,
+            one_of
+        },
+        combinator::{
+            all_consuming,
+            map,
+            map_res,
+            opt,
+            verify
+        },
+        error::VerboseError,
+        multi::{
+            fold_many0,
+            separated_list1
+        },
+        sequence::{
+            delimited,
+            pair,
+            preceded
+        }
+    };
+
+    use crate::util::error::CatchAllError;
+    use crate::util::parse::horz_ws;
+
+    const FUNCTIONS: [&str; 3] = ["min", "max", "abs"];
+
+    #[derive(Clone,Debug)]
+    pub enum Expr {
+        Num(i64),
+        Distance,
+        Neg(Box<Expr>),
+        BinOp(char, Box<Expr>, Box<Expr>),
+        Call(String, Vec<Expr>)
+    }
+
+    impl Expr {
+        pub fn eval(&self, d: i64) -> Result<i64,CatchAllError> {
+            let fail = |what: String| CatchAllError::new(format!("can't evaluate {} at distance {}", what, d));
+            match self {
+                Expr::Num(n) => Ok(*n),
+                Expr::Distance => Ok(d),
+                Expr::Neg(e) => {
+                    let v = e.eval(d)?;
+                    v.checked_neg().ok_or_else(|| fail(format!("-{}", v)))
+                },
+                Expr::BinOp(op, a, b) => {
+                    let (a, b) = (a.eval(d)?, b.eval(d)?);
+                    let result = match op {
+                        '+' => a.checked_add(b),
+                        '-' => a.checked_sub(b),
+                        '*' => a.checked_mul(b),
+                        '/' => a.checked_div(b),
+                        '%' => a.checked_rem(b),
+                        '^' => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+                        _ => None
+                    };
+                    result.ok_or_else(|| fail(format!("{} {} {}", a, op, b)))
+                },
+                Expr::Call(name, args) => {
+                    let vals = args.iter().map(|e| e.eval(d)).collect::<Result<Vec<_>,_>>()?;
+                    let result = match (name.as_str(), &vals[..]) {
+                        ("abs", [v]) => v.checked_abs(),
+                        ("min", _) => vals.iter().min().copied(),
+                        ("max", _) => vals.iter().max().copied(),
+                        _ => None
+                    };
+                    result.ok_or_else(|| fail(format!("{}({:?})", name, vals)))
+                }
+            }
+        }
+    }
+
+    pub fn parse(formula: &str) -> Result<Expr,CatchAllError> {
+        all_consuming(expr)(formula)
+            .map(|(_, e)| e)
+            .map_err(|err| CatchAllError::new(format!("couldn't parse cost formula {}: {}", formula, err)))
+    }
+
+    fn bin_op(op: char, a: Expr, b: Expr) -> Expr {
+        Expr::BinOp(op, Box::new(a), Box::new(b))
+    }
+
+    fn expr(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+        let (input, first) = term(input)?;
+        fold_many0(
+            pair(one_of("+-"), term),
+            move || first.clone(),
+            |acc, (op, e)| bin_op(op, acc, e)
+        )(input)
+    }
+
+    fn term(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+        let (input, first) = unary(input)?;
+        fold_many0(
+            pair(one_of("*/%"), unary),
+            move || first.clone(),
+            |acc, (op, e)| bin_op(op, acc, e)
+        )(input)
+    }
+
+    // binds looser than ^, so -d^2 is -(d^2)
+    fn unary(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+        alt((
+            map(preceded(horz_ws(char('-')), unary), |e| Expr::Neg(Box::new(e))),
+            power
+        ))(input)
+    }
+
+    // right associative, so d^2^3 is d^(2^3)
+    fn power(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+        map(
+            pair(atom, opt(preceded(char('^'), unary))),
+            |(base, exp)| match exp {
+                Some(exp) => bin_op('^', base, exp),
+                None => base
+            }
+        )(input)
+    }
+
+    fn atom(input: &str) -> IResult<&str, Expr, VerboseError<&str>> {
+        horz_ws(alt((
+            map(map_res(digit1, |n: &str| n.parse::<i64>()), Expr::Num),
+            map(
+                pair(
+                    verify(alpha1, |name: &str| FUNCTIONS.contains(&name)),
+                    delimited(char('('), separated_list1(char(','), expr), char(')'))
+                ),
+                |(name, args)| Expr::Call(name.to_string(), args)
+            ),
+            map(char('d'), |_| Expr::Distance),
+            delimited(char('('), expr, char(')'))
+        )))(input)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::parse;
+
+        fn eval(formula: &str, d: i64) -> i64 {
+            parse(formula).unwrap().eval(d).unwrap()
+        }
+
+        #[test]
+        fn precedence() {
+            assert_eq!(eval("1+2*3", 0), 7);
+            assert_eq!(eval("2*3+1", 0), 7);
+            assert_eq!(eval("(1+2)*3", 0), 9);
+            assert_eq!(eval("2*d^2", 3), 18);
+            assert_eq!(eval("-d^2", 3), -9);
+            assert_eq!(eval("-2^2", 0), -4);
+            assert_eq!(eval("d - -d", 3), 6);
+            assert_eq!(eval("7 % 4 * 2", 0), 6);
+        }
+
+        #[test]
+        fn associativity() {
+            assert_eq!(eval("10-4-3", 0), 3);
+            assert_eq!(eval("100/10/5", 0), 2);
+            assert_eq!(eval("2^3^2", 0), 512);
+            assert_eq!(eval("(2^3)^2", 0), 64);
+        }
+
+        #[test]
+        fn whitespace() {
+            assert_eq!(eval(" d * ( d + 1 ) / 2 ", 4), 10);
+            assert_eq!(eval("d*(d+1)/2", 4), 10);
+            assert_eq!(eval("min( d , 10 )", 12), 10);
+        }
+
+        #[test]
+        fn functions() {
+            assert_eq!(eval("min(d, 10) * 2", 12), 20);
+            assert_eq!(eval("max(1, d, 3)", 2), 3);
+            assert_eq!(eval("abs(d - 5)", 2), 3);
+            assert_eq!(eval("max(d)", 4), 4);
+        }
+
+        #[test]
+        fn parse_errors() {
+            for formula in &["", "d+", "1 2", "dd", "foo(d)", "min()", "(d", "d)", "x"] {
+                assert!(parse(formula).is_err(), "{} should not parse", formula);
+            }
+        }
+
+        #[test]
+        fn eval_errors() {
+            let fails = |formula: &str, d: i64| parse(formula).unwrap().eval(d).is_err();
+            assert!(fails("d/(d-1)", 1));
+            assert!(fails("5%(d-1)", 1));
+            assert!(fails("2^(d-2)", 1)); // negative exponent
+            assert!(fails("2^d", 63));
+            assert!(fails("d*d", 4_000_000_000));
+            assert!(fails("abs(d, 1)", 1));
+            assert!(!fails("d/(d-1)", 2));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::rng::Rng;
+
+    const EXAMPLE: [usize; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+    fn new_crabs(poss: &[usize], weights: Option<&[usize]>) -> Crabs {
+        Crabs::new(poss.to_vec(), weights.map(|w| w.to_vec())).unwrap()
+    }
+
+    // checks the closed form and the convex search against brute force, and
+    // gives the minimum they agree on
+    fn solve_all_ways(crabs: &Crabs, model: &CostModel) -> i64 {
+        let cost_calc = |p1: usize, p2: usize| model.cost(distance(p1, p2));
+
+        let brute = brute_force(crabs, &cost_calc).unwrap();
+        let candidates = model.candidates().expect("model should have a closed form");
+        assert_eq!(closed_form(crabs, &cost_calc, candidates).unwrap(), brute, "closed form");
+        assert!(model.is_convex(board_size(crabs).unwrap()).unwrap());
+        assert_eq!(convex_search(crabs, &cost_calc).unwrap(), brute, "search");
+        brute
+    }
+
+    #[test]
+    fn example() {
+        let crabs = new_crabs(&EXAMPLE, None);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Linear), 37);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Triangular), 168);
+        solve_all_ways(&crabs, &CostModel::Quadratic);
+    }
+
+    #[test]
+    fn weighted_crabs() {
+        let crabs = new_crabs(&EXAMPLE, Some(&[1, 2, 3, 1, 1, 1, 1, 1, 1, 9]));
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Linear), 125);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Triangular), 461);
+        solve_all_ways(&crabs, &CostModel::Quadratic);
+    }
+
+    #[test]
+    fn zero_weights() {
+        // the mean lands on the last position, so one of its candidates is
+        // past the furthest crab
+        let crabs = new_crabs(&[0, 1], Some(&[0, 4]));
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Linear), 0);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Triangular), 0);
+    }
+
+    #[test]
+    fn even_crab_count() {
+        // any position from 1 to 3 is optimal for the linear cost
+        let crabs = new_crabs(&[1, 3], None);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Linear), 2);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Triangular), 2);
+
+        let crabs = new_crabs(&[0, 2, 9, 10], None);
+        assert_eq!(solve_all_ways(&crabs, &CostModel::Linear), 17);
+        solve_all_ways(&crabs, &CostModel::Triangular);
+    }
+
+    #[test]
+    fn single_crab() {
+        let crabs = new_crabs(&[5], None);
+        assert_eq!(median_candidates(&crabs), vec![5]);
+        for model in &[CostModel::Linear, CostModel::Triangular, CostModel::Quadratic] {
+            assert_eq!(solve_all_ways(&crabs, model), 0);
+        }
+    }
+
+    #[test]
+    fn random_crabs() {
+        let mut rng = Rng::new(7);
+        for _ in 0..200 {
+            let n = 1 + rng.below(12) as usize;
+            let spread = [1, 3, 10, 100][rng.below(4) as usize];
+            let poss: Vec<usize> = (0..n).map(|_| rng.below(spread + 1) as usize).collect();
+            let weights: Vec<usize> = (0..n).map(|_| rng.below(6) as usize).collect();
+            for weights in &[None, Some(&weights[..])] {
+                let crabs = new_crabs(&poss, *weights);
+                for model in &[CostModel::Linear, CostModel::Triangular, CostModel::Quadratic] {
+                    solve_all_ways(&crabs, model);
+                }
+            }
+        }
+    }
 }
@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::BufReader;
 
 extern crate clap;
-use clap::{Arg, App};
+use clap::{Arg, App, SubCommand};
 
 mod util;
 
@@ -18,11 +18,21 @@ mod day7;
 
 use crate::day1::run as run_day_1;
 use crate::day2::run as run_day_2;
+use crate::day2::args as day_2_args;
 use crate::day3::run as run_day_3;
+use crate::day3::args as day_3_args;
 use crate::day4::run as run_day_4;
+use crate::day4::args as day_4_args;
+use crate::day4::serve_args as day_4_serve_args;
+use crate::day4::serve_bingo;
 use crate::day5::run as run_day_5;
+use crate::day5::args as day_5_args;
+use crate::day5::query_args as day_5_query_args;
+use crate::day5::query_vents;
 use crate::day6::run as run_day_6;
+use crate::day6::args as day_6_args;
 use crate::day7::run as run_day_7;
+use crate::day7::args as day_7_args;
 
 
 fn main() -> Result<(), CatchAllError> {
@@ -33,12 +43,28 @@ fn main() -> Result<(), CatchAllError> {
              .help("Uses examples/day_<n>.txt instead of regular input"))
         .arg(Arg::with_name("day_number")
              .help("Sets which day to run"))
+        .args(&day_2_args())
+        .args(&day_3_args())
+        .args(&day_4_args())
+        .args(&day_5_args())
+        .args(&day_6_args())
+        .args(&day_7_args())
+        .subcommand(SubCommand::with_name("serve-bingo")
+                    .about("Hosts a game of bingo on localhost using the day 4 boards and draws")
+                    .args(&day_4_serve_args()))
+        .subcommand(SubCommand::with_name("query-vents")
+                    .about("Answers questions about the day 5 vent lines")
+                    .args(&day_5_query_args()))
         .get_matches();
 
-    let day_number: usize = matches.value_of("day_number")
-        .ok_or(CatchAllError::new("expected day_number argument".to_string()))?
-        .parse::<usize>()
-        .map_err(|_| CatchAllError::new("day_number must be an int".to_string()))?;
+    let day_number: usize = match matches.subcommand_name() {
+        Some("serve-bingo") => 4,
+        Some("query-vents") => 5,
+        _ => matches.value_of("day_number")
+            .ok_or(CatchAllError::new("expected day_number argument".to_string()))?
+            .parse::<usize>()
+            .map_err(|_| CatchAllError::new("day_number must be an int".to_string()))?
+    };
 
     let use_example: bool = matches.is_present("example");
 
@@ -51,14 +77,20 @@ fn main() -> Result<(), CatchAllError> {
 
     env_logger::init();
 
+    match matches.subcommand() {
+        ("serve-bingo", Some(serve_matches)) => return serve_bingo(input_reader, &matches, serve_matches),
+        ("query-vents", Some(query_matches)) => return query_vents(input_reader, &matches, query_matches),
+        _ => ()
+    }
+
     match day_number {
         1 => Ok(run_day_1(input_reader)),
-        2 => Ok(run_day_2(input_reader)),
-        3 => Ok(run_day_3(input_reader)),
-        4 => Ok(run_day_4(input_reader)),
-        5 => Ok(run_day_5(input_reader)),
-        6 => Ok(run_day_6(input_reader)),
-        7 => Ok(run_day_7(input_reader)),
+        2 => Ok(run_day_2(input_reader, &matches)),
+        3 => Ok(run_day_3(input_reader, &matches)),
+        4 => Ok(run_day_4(input_reader, &matches)),
+        5 => Ok(run_day_5(input_reader, &matches)),
+        6 => Ok(run_day_6(input_reader, &matches)),
+        7 => Ok(run_day_7(input_reader, &matches)),
         _ => Err(CatchAllError::new("unknown day number".to_string()))
     }
 }
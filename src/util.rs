@@ -1,2 +1,4 @@
+pub mod bignum;
 pub mod error;
 pub mod parse;
+pub mod rng;
@@ -0,0 +1,152 @@
+use std::fmt;
+
+// base 10^9 limbs keep printing in decimal trivial
+const BASE: u64 = 1_000_000_000;
+
+// an arbitrary precision unsigned int, just big enough for counting things.
+// limbs are little endian and never have trailing zeros, so zero is empty.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn from_u64(mut n: u64) -> BigUint {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push((n % BASE) as u32);
+            n /= BASE;
+        }
+        BigUint { limbs }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn add(&self, other: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0;
+        for i in 0..len {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push((sum % BASE) as u32);
+            carry = sum / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry as u32);
+        }
+        BigUint { limbs }
+    }
+
+    // schoolbook multiplication, fine for the sizes we deal with
+    pub fn mul(&self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        let mut acc = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = acc[i + j] + a as u64 * b as u64 + carry;
+                acc[i + j] = cur % BASE;
+                carry = cur / BASE;
+            }
+            let mut k = i + other.limbs.len();
+            while carry > 0 {
+                let cur = acc[k] + carry;
+                acc[k] = cur % BASE;
+                carry = cur / BASE;
+                k += 1;
+            }
+        }
+        while acc.last() == Some(&0) {
+            acc.pop();
+        }
+        BigUint { limbs: acc.into_iter().map(|l| l as u32).collect() }
+    }
+
+    // approximate, and infinite past f64's range
+    pub fn to_f64(&self) -> f64 {
+        self.limbs.iter().rev().fold(0.0, |acc, &limb| acc * BASE as f64 + limb as f64)
+    }
+
+    // division by a small int, rounding down
+    pub fn div_small(&self, d: u32) -> BigUint {
+        let mut limbs = vec![0; self.limbs.len()];
+        let mut rem = 0;
+        for (i, &limb) in self.limbs.iter().enumerate().rev() {
+            let cur = rem * BASE + limb as u64;
+            limbs[i] = (cur / d as u64) as u32;
+            rem = cur % d as u64;
+        }
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        BigUint { limbs }
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limbs.split_last() {
+            None => write!(f, "0"),
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{:09}", limb)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: u64) -> BigUint {
+        BigUint::from_u64(n)
+    }
+
+    #[test]
+    fn zero() {
+        assert!(BigUint::zero().is_zero());
+        assert_eq!(big(0), BigUint::zero());
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(big(0).add(&big(0)), BigUint::zero());
+        assert_eq!(big(123).mul(&big(0)), BigUint::zero());
+        assert_eq!(big(0).mul(&big(123)), BigUint::zero());
+        assert_eq!(big(5).div_small(7), BigUint::zero());
+        assert_eq!(big(0).to_f64(), 0.0);
+    }
+
+    #[test]
+    fn limb_boundaries() {
+        assert_eq!(big(999_999_999).add(&big(1)).to_string(), "1000000000");
+        assert_eq!(big(1_000_000_000).limbs, vec![0, 1]);
+        assert_eq!(big(999_999_999_999_999_999).add(&big(1)).to_string(), "1000000000000000000");
+        assert_eq!(big(1_000_000_000_000_000_000).limbs, vec![0, 0, 1]);
+        assert_eq!(big(1_000_000_000).mul(&big(1_000_000_000)), big(1_000_000_000_000_000_000));
+        assert_eq!(big(999_999_999).mul(&big(999_999_999)), big(999_999_998_000_000_001));
+        assert_eq!(big(1_000_000_000_000_000_000).div_small(1_000_000_000), big(1_000_000_000));
+        assert_eq!(big(1_000_000_000).div_small(3), big(333_333_333));
+        assert_eq!(big(u64::MAX).to_string(), u64::MAX.to_string());
+        assert_eq!(big(u64::MAX).add(&big(u64::MAX)).to_string(), (u64::MAX as u128 * 2).to_string());
+        assert_eq!(big(u64::MAX).mul(&big(u64::MAX)).to_string(), (u64::MAX as u128 * u64::MAX as u128).to_string());
+        assert_eq!(big(1_000_000_000_000_000_000).to_f64(), 1e18);
+    }
+
+    #[test]
+    fn display_pads_inner_limbs() {
+        assert_eq!(big(1_000_000_001).to_string(), "1000000001");
+        assert_eq!(big(5_000_000_000_000_000_007).to_string(), "5000000000000000007");
+        assert_eq!(big(1_000_000_000_000_000_000).add(&big(42_000_000_000)).to_string(), "1000000042000000000");
+        assert_eq!(big(7).to_string(), "7");
+    }
+}
@@ -14,7 +14,7 @@ impl CatchAllError {
 
 impl fmt::Display for CatchAllError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "CatchAllError")
+        write!(f, "{}", self.reason)
     }
 }
 
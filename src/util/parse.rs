@@ -1,16 +1,22 @@
 use nom::{
   IResult,
-  combinator::map_res,
+  combinator::{
+      map_res,
+      opt,
+      recognize
+  },
   error::{
       ParseError,
       VerboseError
   },
   sequence::{
+      pair,
       terminated,
       delimited,
 //      preceded
   },
   character::complete::{
+      char,
       multispace0,
       digit1,
       space0
@@ -69,3 +75,11 @@ pub fn usize_parser<'a>(input: &'a str) -> IResult<&'a str, usize, VerboseError<
         |out: &str| out.parse::<usize>()
     )(input)
 }
+
+pub fn i64_parser(input: &str) -> IResult<&str, i64, VerboseError<&str>>
+{
+    map_res(
+        recognize(pair(opt(char('-')), digit1)),
+        |out: &str| out.parse::<i64>()
+    )(input)
+}